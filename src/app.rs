@@ -1,75 +1,242 @@
 use eframe::egui::{self, Key, KeyboardShortcut, Modifiers};
 use serde::{Deserialize, Serialize};
 use std::{
+    fmt,
+    ops::RangeInclusive,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{runtime::Runtime, sync::mpsc, task::JoinHandle};
-use tracing::error;
+use tokio::runtime::Runtime;
+use tracing::{error, info};
 
 use crate::{
     InputHandler,
+    config::{self, ConfigFile},
+    engine::{
+        Clicker, ClickerBuilder, ClickerConfig, Ending, Event, LiveSettings, MAX_CPS, MIN_CPS,
+        Status,
+    },
+    evemu::{self, ScreenSize},
+    files,
+    focus::{AppRule, FocusWatcher, FocusedWindow, RuleField},
     input::{ClickAction, Coordinates, MouseButton},
-    interval::{IntervalMode, Jitter, TimeInterval},
+    interval::{BurstPattern, Easing, IntervalMode, Ramp, TimeInterval},
+    keys,
+    learned::{self, LearnedProfile, ProfileRecorder},
+    macros::{self, Macro, MacroRecorder, Playback},
+    migrate,
+    profiles::{self, Profile, ProfileSettings},
+    schedule::{TimeOfDay, format_countdown},
+    screen::{PixelCondition, Screen},
+    script::{self, Script},
+    sequence::{Sequence, Step, StepKind},
+    stats::{ClickStats, histogram},
+    template::{ImageTarget, MissAction, TargetSearch, Template},
+    widgets,
 };
 
+/// Matches the app name passed to `eframe::run_native`
+const WINDOW_TITLE: &str = "Click";
+
+/// Storage key that saved settings are moved to when they can't be read, so
+/// they aren't lost when the defaults are saved over them
+const UNREADABLE_KEY: &str = "app.unreadable";
+
 #[derive(Deserialize, Serialize)]
 #[serde(default)]
 pub struct ClickApp {
+    /// Schema version of the saved settings, see [`migrate`]
+    version: u32,
+    run_mode: RunMode,
     interval_mode: IntervalMode,
     time_interval: TimeInterval,
-    cps: u16,
+    cps: f64,
+    cps_step: f64,
+    ramp: Ramp,
+    use_ramp: bool,
     jitter: u16,
     use_jitter: bool,
+    seed: u64,
+    use_seed: bool,
+    #[serde(deserialize_with = "learned::deserialize_usable")]
+    learned_profile: Option<LearnedProfile>,
+    use_learned_profile: bool,
 
     mouse_button: MouseButton,
     click_type: ClickAction,
     num_clicks: u32,
     use_num_clicks: bool,
+    burst: BurstPattern,
+    use_burst: bool,
+    num_bursts: u32,
+    use_num_bursts: bool,
+    run_duration: TimeInterval,
+    use_run_duration: bool,
+    stop_at: TimeOfDay,
+    use_stop_at: bool,
+    start_at: TimeOfDay,
+    use_start_at: bool,
+    countdown_secs: u8,
+    use_countdown: bool,
+    countdown_on_hotkey: bool,
     location: Coordinates,
     use_location: bool,
 
+    recorded_macro: Macro,
+    macro_speed: f64,
+    macro_loops: u32,
+    use_macro_loops: bool,
+
+    sequence: Sequence,
+
+    pixel_condition: PixelCondition,
+    use_pixel_condition: bool,
+    image_target: ImageTarget,
+    use_image_target: bool,
+
+    script: String,
+    script_runs: u32,
+    use_script_runs: bool,
+
     start_shortcut: KeyboardShortcut,
     stop_shortcut: KeyboardShortcut,
+    pause_shortcut: KeyboardShortcut,
+    faster_shortcut: KeyboardShortcut,
+    slower_shortcut: KeyboardShortcut,
+    record_shortcut: KeyboardShortcut,
+    stop_record_shortcut: KeyboardShortcut,
+
+    /// Saved setups; the fields above hold the settings of the active one
+    profiles: Vec<Profile>,
+    active_profile: usize,
+    /// Profiles switched to by the focused window, first match wins
+    app_rules: Vec<AppRule>,
+    use_app_rules: bool,
+    /// New name being typed for the active profile
+    #[serde(skip)]
+    profile_rename: Option<String>,
+    /// Waiting for a key press to use as the active profile's hotkey
+    #[serde(skip)]
+    binding_profile_shortcut: bool,
+
+    #[serde(skip)]
+    focus: Option<FocusWatcher>,
+    #[serde(skip)]
+    focused: Option<FocusedWindow>,
+    /// Windows focused lately, newest first, offered when adding a rule
+    #[serde(skip)]
+    recent_windows: Vec<FocusedWindow>,
+    #[serde(skip)]
+    focus_error: Option<String>,
+    /// The clicker was paused because focus left the matching windows
+    #[serde(skip)]
+    focus_paused: bool,
+
+    #[serde(skip)]
+    config: Option<ConfigFile>,
+    /// Why the config file couldn't be read, watched or written
+    #[serde(skip)]
+    config_error: Option<String>,
+    /// Why the stored settings couldn't be loaded, along with their text until
+    /// it's been moved aside
+    #[serde(skip)]
+    settings_error: Option<String>,
+    #[serde(skip)]
+    unreadable_settings: Option<String>,
 
     #[serde(skip)]
     #[serde(default)]
     clicker: ClickerState,
+
+    #[serde(skip)]
+    profile_recorder: Option<ProfileRecorder>,
+    #[serde(skip)]
+    profile_error: Option<String>,
+
+    #[serde(skip)]
+    macro_recorder: Option<MacroRecorder>,
+    #[serde(skip)]
+    macro_error: Option<String>,
+    #[serde(skip)]
+    sequence_error: Option<String>,
+    #[serde(skip)]
+    color_pick: Option<(Instant, PickTarget)>,
+    /// Why picking a color or a target image failed
+    #[serde(skip)]
+    screen_error: Option<String>,
+
+    #[serde(skip)]
+    tab: Tab,
+    #[serde(skip)]
+    stats: ClickStats,
+    /// Last title sent to the window, so it's only updated when it changes
+    #[serde(skip)]
+    window_title: String,
+    #[serde(skip)]
+    timing_preview: Option<TimingPreview>,
 }
 
-enum ClickerStatus {
-    Completed,
-    Error(String),
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Tab {
+    #[default]
+    Settings,
+    Statistics,
+    Macro,
+    Sequence,
+    Script,
+    Rules,
 }
 
-struct ClickerConfig {
-    base_interval: Duration,
-    mouse_button: MouseButton,
-    click_action: ClickAction,
-    use_jitter: bool,
-    jitter: u16,
-    use_location: bool,
-    location: Coordinates,
-    use_num_clicks: bool,
-    num_clicks: u32,
+/// Where a color picked from the screen goes
+#[derive(Clone, Copy, PartialEq)]
+enum PickTarget {
+    Settings,
+    Step(usize),
+}
+
+/// Time to move the pointer over the point before its color is picked
+const PICK_DELAY: Duration = Duration::from_secs(3);
+
+/// Number of recently focused windows offered for new rules
+const RECENT_WINDOWS: usize = 5;
+
+/// What the clicker does on each run
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+enum RunMode {
+    Clicks,
+    Macro,
+    Sequence,
+    Script,
 }
 
-async fn wait_for_tick(
-    config: &ClickerConfig,
-    jitter_gen: &mut Jitter,
-    interval: &mut tokio::time::Interval,
-    next_tick: &mut tokio::time::Instant,
-) {
-    if config.use_jitter && config.jitter > 0 {
-        let delay = jitter_gen.next(config.base_interval, config.jitter);
-        *next_tick += delay;
-        tokio::time::sleep_until(*next_tick).await;
-    } else {
-        interval.tick().await;
+impl fmt::Display for RunMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunMode::Clicks => write!(f, "Click"),
+            RunMode::Macro => write!(f, "Play macro"),
+            RunMode::Sequence => write!(f, "Run sequence"),
+            RunMode::Script => write!(f, "Run script"),
+        }
     }
 }
 
-impl ClickerConfig {
+impl RunMode {
+    fn all() -> [RunMode; 4] {
+        [
+            RunMode::Clicks,
+            RunMode::Macro,
+            RunMode::Sequence,
+            RunMode::Script,
+        ]
+    }
+}
+
+/// Number of simulated clicks in the timing preview
+const PREVIEW_CLICKS: usize = 3000;
+/// Number of those clicks drawn on the preview timeline
+const PREVIEW_TIMELINE: usize = 200;
+
+impl LiveSettings {
     fn from_app(app: &ClickApp) -> Self {
         Self {
             base_interval: app.calculate_interval(),
@@ -79,19 +246,235 @@ impl ClickerConfig {
             jitter: app.jitter,
             use_location: app.use_location,
             location: app.location,
-            use_num_clicks: app.use_num_clicks,
-            num_clicks: app.num_clicks,
         }
     }
 }
 
+impl ClickerConfig {
+    /// Snapshots the app's settings, holding off the first click for at least `countdown`
+    fn from_app(app: &ClickApp, countdown: Duration) -> anyhow::Result<Self> {
+        // Without a fixed seed, pick a fresh one so the run can still be reproduced later
+        let seed = if app.use_seed {
+            app.seed
+        } else {
+            rand::random()
+        };
+
+        let start_delay = if app.use_start_at {
+            app.start_at.next_after(countdown)
+        } else {
+            countdown
+        };
+
+        // Stop conditions combine with OR, so whichever comes first wins
+        let run_limit = [
+            app.use_run_duration.then(|| app.run_duration.to_duration()),
+            app.use_stop_at
+                .then(|| app.stop_at.next_after(start_delay) - start_delay),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+
+        let mut builder = Self::timing(app)
+            .button(app.mouse_button)
+            .action(app.click_type)
+            .seed(seed)
+            .start_delay(start_delay);
+        if app.use_location {
+            builder = builder.location(app.location);
+        }
+        if let Some(run_limit) = run_limit {
+            builder = builder.run_limit(run_limit);
+        }
+
+        match app.run_mode {
+            RunMode::Clicks => {
+                if app.use_pixel_condition {
+                    builder = builder.pixel_condition(app.pixel_condition);
+                }
+                if app.use_image_target {
+                    builder = builder.image_target(app.image_target.clone());
+                }
+            }
+            RunMode::Macro => {
+                if !app.recorded_macro.is_empty() {
+                    builder = builder.playback(Playback {
+                        recording: app.recorded_macro.clone(),
+                        speed: app.macro_speed,
+                        loops: app.use_macro_loops.then_some(app.macro_loops),
+                    });
+                }
+            }
+            RunMode::Sequence => {
+                if let Ok(program) = app.sequence.compile() {
+                    builder = builder.program(program);
+                }
+            }
+            RunMode::Script => {
+                if let Ok(script) = Script::compile(&app.script) {
+                    builder = builder.script(script);
+                }
+                if app.use_script_runs {
+                    builder = builder.script_runs(app.script_runs);
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    /// Just the settings that decide when each click happens, which is all the timing preview needs
+    fn timing(app: &ClickApp) -> ClickerBuilder {
+        let mut builder = ClickerConfig::builder().interval(app.calculate_interval());
+        if app.use_ramp {
+            builder = builder.ramp(app.ramp);
+        }
+        if app.use_jitter {
+            builder = builder.jitter(app.jitter);
+        }
+        if let Some(profile) = &app.learned_profile
+            && app.use_learned_profile
+        {
+            builder = builder.learned_profile(profile.clone());
+        }
+        if app.use_num_clicks {
+            builder = builder.click_limit(app.num_clicks);
+        }
+        if app.use_burst {
+            builder = builder.burst(app.burst);
+        }
+        if app.use_num_bursts {
+            builder = builder.burst_limit(app.num_bursts);
+        }
+        builder
+    }
+}
+
+/// Reads the config file, returning the settings if they changed and which of
+/// them were out of range and had to be clamped
+fn read_config(config: &mut ConfigFile) -> anyhow::Result<(Option<ClickApp>, Option<String>)> {
+    let mut clamped = Vec::new();
+    let loaded = config.read(|settings| {
+        let mut loaded = ClickApp::from_saved(settings)?;
+        clamped = loaded.clamp_to_ranges();
+        Ok(loaded)
+    })?;
+    let clamped =
+        (!clamped.is_empty()).then(|| format!("Out of range, so clamped: {}", clamped.join(", ")));
+    Ok((loaded, clamped))
+}
+
+/// Clamps settings into range, keeping a note of each one that wasn't
+#[derive(Default)]
+struct RangeCheck {
+    clamped: Vec<String>,
+}
+
+impl RangeCheck {
+    fn clamp<T: PartialOrd + Copy + fmt::Display>(
+        &mut self,
+        name: &str,
+        value: &mut T,
+        range: RangeInclusive<T>,
+    ) {
+        if range.contains(value) {
+            return;
+        }
+        // NaN isn't above the range either, so it ends up at the bottom
+        let within = if *value > *range.end() {
+            *range.end()
+        } else {
+            *range.start()
+        };
+        self.clamped.push(format!("{name} {value} to {within}"));
+        *value = within;
+    }
+
+    /// Checks the settings a profile holds, naming them after `prefix`
+    fn profile_settings(&mut self, prefix: &str, settings: &mut ProfileSettings) {
+        self.clamp(
+            &format!("{prefix}cps"),
+            &mut settings.cps,
+            MIN_CPS..=MAX_CPS,
+        );
+        self.clamp(
+            &format!("{prefix}ramp.start_cps"),
+            &mut settings.ramp.start_cps,
+            MIN_CPS..=MAX_CPS,
+        );
+        self.clamp(
+            &format!("{prefix}ramp.ramp_down_clicks"),
+            &mut settings.ramp.ramp_down_clicks,
+            1..=u32::MAX,
+        );
+        self.clamp(&format!("{prefix}jitter"), &mut settings.jitter, 0..=1000);
+        self.clamp(
+            &format!("{prefix}num_clicks"),
+            &mut settings.num_clicks,
+            1..=u32::MAX,
+        );
+        self.clamp(
+            &format!("{prefix}num_bursts"),
+            &mut settings.num_bursts,
+            1..=u32::MAX,
+        );
+    }
+}
+
+/// The settings a timing preview was simulated from, so it's only redone when they change
+#[derive(Clone, PartialEq)]
+struct PreviewKey {
+    interval: Duration,
+    seed: u64,
+    ramp: Option<Ramp>,
+    jitter: Option<u16>,
+    learned_profile: Option<LearnedProfile>,
+    num_clicks: Option<u32>,
+    burst: Option<BurstPattern>,
+    num_bursts: Option<u32>,
+}
+
+impl PreviewKey {
+    fn from_app(app: &ClickApp) -> Self {
+        Self {
+            interval: app.calculate_interval(),
+            seed: app.seed,
+            ramp: app.use_ramp.then_some(app.ramp),
+            jitter: app.use_jitter.then_some(app.jitter),
+            learned_profile: app
+                .learned_profile
+                .clone()
+                .filter(|_| app.use_learned_profile),
+            num_clicks: app.use_num_clicks.then_some(app.num_clicks),
+            burst: app.use_burst.then_some(app.burst),
+            num_bursts: app.use_num_bursts.then_some(app.num_bursts),
+        }
+    }
+}
+
+/// Simulated delays in milliseconds, or why the settings couldn't be simulated
+struct TimingPreview {
+    key: PreviewKey,
+    delays: Result<Vec<f64>, String>,
+}
+
 struct ClickerState {
-    stop_sender: Option<mpsc::Sender<()>>,
-    status_receiver: Option<mpsc::Receiver<ClickerStatus>>,
-    task_handle: Option<JoinHandle<()>>,
+    /// The current run, if there is one
+    run: Option<Clicker>,
     runtime: Arc<Runtime>,
     input_handler: Option<Arc<Mutex<InputHandler>>>,
     is_running: bool,
+    /// When the running clicker was paused, if it is
+    paused_at: Option<Instant>,
+    click_limit: Option<u32>,
+    last_seed: Option<u64>,
+    /// When a delayed run clicks for the first time
+    starts_at: Option<Instant>,
+    /// When a time-limited run stops on its own
+    stops_at: Option<Instant>,
+    /// Why the last run failed or couldn't start
+    last_error: Option<String>,
 }
 
 impl Default for ClickerState {
@@ -104,74 +487,494 @@ impl Default for ClickerState {
         let runtime = Arc::new(Runtime::new().expect("Failed to create tokio runtime"));
 
         Self {
-            stop_sender: None,
-            status_receiver: None,
-            task_handle: None,
+            run: None,
             runtime,
             input_handler,
             is_running: false,
+            paused_at: None,
+            click_limit: None,
+            last_seed: None,
+            starts_at: None,
+            stops_at: None,
+            last_error: None,
         }
     }
 }
 
 impl Default for ClickApp {
     fn default() -> Self {
+        let settings = ProfileSettings::default();
         Self {
-            interval_mode: IntervalMode::Time,
-            time_interval: TimeInterval::default(),
-            cps: 20,
-            jitter: 0,
-            use_jitter: false,
-            mouse_button: MouseButton::Left,
-            click_type: ClickAction::Single,
-            num_clicks: 100,
-            use_num_clicks: false,
-            location: Coordinates::default(),
-            use_location: false,
+            version: migrate::VERSION,
+            run_mode: RunMode::Clicks,
+            interval_mode: settings.interval_mode,
+            time_interval: settings.time_interval,
+            cps: settings.cps,
+            cps_step: 1.0,
+            ramp: settings.ramp,
+            use_ramp: settings.use_ramp,
+            jitter: settings.jitter,
+            use_jitter: settings.use_jitter,
+            seed: 0,
+            use_seed: false,
+            learned_profile: None,
+            use_learned_profile: false,
+            mouse_button: settings.mouse_button,
+            click_type: settings.click_type,
+            num_clicks: settings.num_clicks,
+            use_num_clicks: settings.use_num_clicks,
+            burst: BurstPattern::default(),
+            use_burst: false,
+            num_bursts: settings.num_bursts,
+            use_num_bursts: settings.use_num_bursts,
+            run_duration: settings.run_duration,
+            use_run_duration: settings.use_run_duration,
+            stop_at: settings.stop_at,
+            use_stop_at: settings.use_stop_at,
+            start_at: TimeOfDay::default(),
+            use_start_at: false,
+            countdown_secs: 3,
+            use_countdown: false,
+            countdown_on_hotkey: false,
+            location: settings.location,
+            use_location: settings.use_location,
+            recorded_macro: Macro::default(),
+            macro_speed: 1.0,
+            macro_loops: 1,
+            use_macro_loops: true,
+            sequence: Sequence::default(),
+            pixel_condition: PixelCondition::default(),
+            use_pixel_condition: false,
+            image_target: ImageTarget::default(),
+            use_image_target: false,
+            script: script::EXAMPLE.to_owned(),
+            script_runs: 1,
+            use_script_runs: false,
             start_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F6),
             stop_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F7),
+            pause_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F8),
+            faster_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F10),
+            slower_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F9),
+            record_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F11),
+            stop_record_shortcut: KeyboardShortcut::new(Modifiers::NONE, Key::F12),
+            profiles: vec![Profile::new(profiles::DEFAULT_NAME.to_owned(), settings)],
+            active_profile: 0,
+            app_rules: Vec::new(),
+            use_app_rules: false,
+            profile_rename: None,
+            binding_profile_shortcut: false,
+            focus: None,
+            focused: None,
+            recent_windows: Vec::new(),
+            focus_error: None,
+            focus_paused: false,
+            config: None,
+            config_error: None,
+            settings_error: None,
+            unreadable_settings: None,
             clicker: ClickerState::default(),
+            profile_recorder: None,
+            profile_error: None,
+            macro_recorder: None,
+            macro_error: None,
+            sequence_error: None,
+            color_pick: None,
+            screen_error: None,
+            tab: Tab::Settings,
+            stats: ClickStats::default(),
+            window_title: WINDOW_TITLE.to_owned(),
+            timing_preview: None,
         }
     }
 }
 
 impl ClickApp {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        if let Some(storage) = cc.storage {
-            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
-        } else {
-            Default::default()
+        let saved = cc
+            .storage
+            .and_then(|storage| storage.get_string(eframe::APP_KEY));
+        let mut app = match saved {
+            Some(text) => match migrate::from_ron(&text).and_then(Self::from_saved) {
+                Ok(mut app) => {
+                    app.clamp_to_ranges();
+                    app
+                }
+                Err(e) => {
+                    error!("Couldn't load the saved settings: {e:#}");
+                    Self {
+                        settings_error: Some(format!("{e:#}")),
+                        unreadable_settings: Some(text),
+                        ..Default::default()
+                    }
+                }
+            },
+            None => Self::default(),
+        };
+        app.fix_profiles();
+        app.open_config(&cc.egui_ctx);
+        app
+    }
+
+    /// Loads settings saved by this or any older version
+    pub(crate) fn from_saved(settings: serde_json::Value) -> anyhow::Result<Self> {
+        Ok(serde_json::from_value(migrate::upgrade(settings)?)?)
+    }
+
+    /// Makes sure there's a profile and the active one exists, whatever was loaded
+    fn fix_profiles(&mut self) {
+        if self.profiles.is_empty() {
+            let settings = self.profile_settings();
+            self.profiles
+                .push(Profile::new(profiles::DEFAULT_NAME.to_owned(), settings));
+        }
+        self.active_profile = self.active_profile.min(self.profiles.len() - 1);
+    }
+
+    /// Pulls numbers edited outside the app back into the ranges the settings
+    /// allow, returning the ones that were out of range
+    fn clamp_to_ranges(&mut self) -> Vec<String> {
+        let mut check = RangeCheck::default();
+
+        let mut settings = self.profile_settings();
+        check.profile_settings("", &mut settings);
+        self.apply_profile_settings(settings);
+        for (index, profile) in self.profiles.iter_mut().enumerate() {
+            check.profile_settings(
+                &format!("profiles[{index}].settings."),
+                &mut profile.settings,
+            );
+        }
+
+        check.clamp("cps_step", &mut self.cps_step, MIN_CPS..=MAX_CPS);
+        check.clamp("burst.clicks", &mut self.burst.clicks, 1..=u32::MAX);
+        check.clamp("countdown_secs", &mut self.countdown_secs, 1..=60);
+        check.clamp(
+            "macro_speed",
+            &mut self.macro_speed,
+            macros::MIN_SPEED..=macros::MAX_SPEED,
+        );
+        check.clamp("macro_loops", &mut self.macro_loops, 1..=u32::MAX);
+        check.clamp(
+            "pixel_condition.radius",
+            &mut self.pixel_condition.radius,
+            0..=50,
+        );
+        check.clamp(
+            "image_target.confidence",
+            &mut self.image_target.confidence,
+            0.5..=1.0,
+        );
+        check.clamp(
+            "image_target.scatter",
+            &mut self.image_target.scatter,
+            0..=200,
+        );
+        check.clamp("script_runs", &mut self.script_runs, 1..=u32::MAX);
+        check.clamped
+    }
+
+    /// Starts watching the config file, taking its settings over the stored
+    /// ones, or writes it out if there isn't one yet
+    fn open_config(&mut self, ctx: &egui::Context) {
+        let Some(path) = config::path() else {
+            return;
+        };
+        let mut config = match ConfigFile::watch(path, ctx.clone()) {
+            Ok(config) => config,
+            Err(e) => {
+                self.config_error = Some(e.to_string());
+                return;
+            }
+        };
+
+        match read_config(&mut config) {
+            Ok((Some(loaded), clamped)) => {
+                self.reload(loaded);
+                self.config_error = clamped;
+            }
+            Ok((None, _)) => {
+                if let Err(e) = config.write(self) {
+                    self.config_error = Some(e.to_string());
+                }
+            }
+            Err(e) => self.config_error = Some(e.to_string()),
+        }
+        self.config = Some(config);
+    }
+
+    /// Applies settings from the config file when it changes on disk
+    fn poll_config(&mut self) {
+        let Some(config) = &mut self.config else {
+            return;
+        };
+        if !config.changed() {
+            return;
+        }
+
+        match read_config(config) {
+            Ok((loaded, clamped)) => {
+                if let Some(loaded) = loaded {
+                    info!("Reloaded settings from {}", config.path().display());
+                    self.reload(loaded);
+                }
+                self.config_error = clamped;
+            }
+            Err(e) => self.config_error = Some(e.to_string()),
+        }
+    }
+
+    /// Takes the settings from `loaded`, keeping everything that isn't saved,
+    /// like a running clicker or recording
+    fn reload(&mut self, loaded: ClickApp) {
+        *self = ClickApp {
+            profile_rename: None,
+            binding_profile_shortcut: false,
+            focus: self.focus.take(),
+            focused: self.focused.take(),
+            recent_windows: std::mem::take(&mut self.recent_windows),
+            focus_error: self.focus_error.take(),
+            focus_paused: self.focus_paused,
+            config: self.config.take(),
+            config_error: self.config_error.take(),
+            settings_error: self.settings_error.take(),
+            unreadable_settings: self.unreadable_settings.take(),
+            clicker: std::mem::take(&mut self.clicker),
+            profile_recorder: self.profile_recorder.take(),
+            profile_error: self.profile_error.take(),
+            macro_recorder: self.macro_recorder.take(),
+            macro_error: self.macro_error.take(),
+            sequence_error: self.sequence_error.take(),
+            color_pick: self.color_pick.take(),
+            screen_error: self.screen_error.take(),
+            tab: self.tab,
+            stats: std::mem::take(&mut self.stats),
+            window_title: std::mem::take(&mut self.window_title),
+            ..loaded
+        };
+        self.fix_profiles();
+    }
+
+    fn profile_settings(&self) -> ProfileSettings {
+        ProfileSettings {
+            interval_mode: self.interval_mode,
+            time_interval: self.time_interval,
+            cps: self.cps,
+            ramp: self.ramp,
+            use_ramp: self.use_ramp,
+            jitter: self.jitter,
+            use_jitter: self.use_jitter,
+            mouse_button: self.mouse_button,
+            click_type: self.click_type,
+            location: self.location,
+            use_location: self.use_location,
+            num_clicks: self.num_clicks,
+            use_num_clicks: self.use_num_clicks,
+            num_bursts: self.num_bursts,
+            use_num_bursts: self.use_num_bursts,
+            run_duration: self.run_duration,
+            use_run_duration: self.use_run_duration,
+            stop_at: self.stop_at,
+            use_stop_at: self.use_stop_at,
+        }
+    }
+
+    fn apply_profile_settings(&mut self, settings: ProfileSettings) {
+        self.interval_mode = settings.interval_mode;
+        self.time_interval = settings.time_interval;
+        self.cps = settings.cps;
+        self.ramp = settings.ramp;
+        self.use_ramp = settings.use_ramp;
+        self.jitter = settings.jitter;
+        self.use_jitter = settings.use_jitter;
+        self.mouse_button = settings.mouse_button;
+        self.click_type = settings.click_type;
+        self.location = settings.location;
+        self.use_location = settings.use_location;
+        self.num_clicks = settings.num_clicks;
+        self.use_num_clicks = settings.use_num_clicks;
+        self.num_bursts = settings.num_bursts;
+        self.use_num_bursts = settings.use_num_bursts;
+        self.run_duration = settings.run_duration;
+        self.use_run_duration = settings.use_run_duration;
+        self.stop_at = settings.stop_at;
+        self.use_stop_at = settings.use_stop_at;
+    }
+
+    /// Copies the settings being edited into the active profile
+    fn store_active_profile(&mut self) {
+        let settings = self.profile_settings();
+        self.profiles[self.active_profile].settings = settings;
+    }
+
+    fn switch_profile(&mut self, index: usize) {
+        if index == self.active_profile || index >= self.profiles.len() {
+            return;
+        }
+        self.store_active_profile();
+        self.active_profile = index;
+        self.apply_profile_settings(self.profiles[index].settings.clone());
+        self.profile_rename = None;
+        self.binding_profile_shortcut = false;
+    }
+
+    /// Adds a copy of the active profile, without its hotkey, and switches to it
+    fn duplicate_profile(&mut self) {
+        self.store_active_profile();
+        let active = &self.profiles[self.active_profile];
+        let name = profiles::unique_name(&self.profiles, &active.name);
+        let copy = Profile::new(name, active.settings.clone());
+        self.profiles.push(copy);
+        self.active_profile = self.profiles.len() - 1;
+        self.profile_rename = None;
+        self.binding_profile_shortcut = false;
+    }
+
+    /// Removes the active profile and the rules that switch to it, then
+    /// switches to its neighbor, keeping at least one profile
+    fn delete_profile(&mut self) {
+        if self.profiles.len() < 2 {
+            return;
+        }
+        let deleted = self.profiles.remove(self.active_profile);
+        self.app_rules.retain(|rule| rule.profile != deleted.name);
+        self.active_profile = self.active_profile.min(self.profiles.len() - 1);
+        self.apply_profile_settings(self.profiles[self.active_profile].settings.clone());
+        self.profile_rename = None;
+        self.binding_profile_shortcut = false;
+    }
+
+    /// Uses the next key pressed as the active profile's hotkey, or gives up
+    /// on Escape. Returns whether it was waiting for one, so the key isn't
+    /// also taken as a hotkey.
+    fn bind_profile_shortcut(&mut self, input: &egui::InputState) -> bool {
+        if !self.binding_profile_shortcut {
+            return false;
         }
+        let pressed = input.events.iter().find_map(|event| match event {
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => Some(KeyboardShortcut::new(*modifiers, *key)),
+            _ => None,
+        });
+        if let Some(shortcut) = pressed {
+            if shortcut.logical_key != Key::Escape {
+                self.profiles[self.active_profile].shortcut = Some(shortcut);
+            }
+            self.binding_profile_shortcut = false;
+        }
+        true
     }
 
-    fn perform_click(
-        input_handler: &Arc<Mutex<InputHandler>>,
-        config: &ClickerConfig,
-    ) -> Result<(), String> {
-        input_handler
-            .lock()
-            .map_err(|e| format!("Lock error: {e}"))
-            .and_then(|mut handler| {
-                if config.use_location {
-                    handler.click_at(config.location, config.mouse_button, config.click_action)
+    fn profile_ui(&mut self, ui: &mut egui::Ui, unlocked: bool) {
+        ui.add_enabled_ui(unlocked, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Profile:");
+                let mut selected = self.active_profile;
+                egui::ComboBox::from_id_salt("profile")
+                    .selected_text(&self.profiles[self.active_profile].name)
+                    .show_ui(ui, |ui| {
+                        for (index, profile) in self.profiles.iter().enumerate() {
+                            ui.selectable_value(&mut selected, index, &profile.name);
+                        }
+                    });
+                self.switch_profile(selected);
+
+                if ui
+                    .button("Duplicate")
+                    .on_hover_text("Save the current settings as a new profile")
+                    .clicked()
+                {
+                    self.duplicate_profile();
+                }
+                if ui.button("Rename").clicked() {
+                    self.profile_rename = Some(self.profiles[self.active_profile].name.clone());
+                }
+                if ui
+                    .add_enabled(self.profiles.len() > 1, egui::Button::new("Delete"))
+                    .clicked()
+                {
+                    self.delete_profile();
+                }
+            });
+
+            if let Some(name) = &mut self.profile_rename {
+                let name_taken =
+                    self.profiles.iter().enumerate().any(|(index, profile)| {
+                        index != self.active_profile && profile.name == *name
+                    });
+                let valid = !name.trim().is_empty() && !name_taken;
+                let mut done = false;
+                ui.horizontal(|ui| {
+                    ui.label("New name:");
+                    let response = ui.text_edit_singleline(name);
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                    if ui.add_enabled(valid, egui::Button::new("OK")).clicked()
+                        || (valid && submitted)
+                    {
+                        let profile = &mut self.profiles[self.active_profile];
+                        let old = std::mem::replace(&mut profile.name, name.trim().to_owned());
+                        for rule in &mut self.app_rules {
+                            if rule.profile == old {
+                                rule.profile.clone_from(&profile.name);
+                            }
+                        }
+                        done = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        done = true;
+                    }
+                    if name_taken {
+                        ui.colored_label(ui.visuals().error_fg_color, "Name already used");
+                    }
+                });
+                if done {
+                    self.profile_rename = None;
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Hotkey:");
+                let profile = &mut self.profiles[self.active_profile];
+                let text = if self.binding_profile_shortcut {
+                    "Press a key…".to_owned()
                 } else {
-                    handler.click(config.mouse_button, config.click_action)
+                    profile.shortcut.map_or("None".to_owned(), |shortcut| {
+                        ui.ctx().format_shortcut(&shortcut)
+                    })
+                };
+                if ui
+                    .button(text)
+                    .on_hover_text("Click, then press the key that switches to this profile")
+                    .clicked()
+                {
+                    self.binding_profile_shortcut = true;
                 }
-                .map_err(|e| format!("Click failed: {e}"))
-            })
+                if ui
+                    .add_enabled(profile.shortcut.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    profile.shortcut = None;
+                }
+                ui.add_enabled(
+                    profile.shortcut.is_some(),
+                    egui::Checkbox::new(&mut profile.start_on_shortcut, "Also start clicking"),
+                );
+            });
+        });
     }
 
     fn calculate_interval(&self) -> Duration {
         match self.interval_mode {
             IntervalMode::Time => self.time_interval.to_duration(),
-            IntervalMode::Cps => {
-                let cps = self.cps.max(1) as u64;
-                Duration::from_secs_f64(1.0 / cps as f64)
-            }
+            IntervalMode::Cps => Duration::from_secs_f64(1.0 / self.cps.max(MIN_CPS)),
         }
     }
 
-    fn start_clicker(&mut self, ctx: &egui::Context) {
+    fn start_clicker(&mut self, ctx: &egui::Context, from_hotkey: bool) {
         if self.clicker.is_running {
             return;
         }
@@ -184,112 +987,1513 @@ impl ClickApp {
             }
         };
 
-        let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
-        let (status_tx, status_rx) = mpsc::channel::<ClickerStatus>(1);
+        self.clicker.last_error = None;
+        let checked = match self.run_mode {
+            RunMode::Sequence => self.sequence.compile().err().map(|e| (e, Tab::Sequence)),
+            RunMode::Script => Script::compile(&self.script)
+                .err()
+                .map(|e| (e, Tab::Script)),
+            RunMode::Macro => self.recorded_macro.is_empty().then(|| {
+                (
+                    anyhow::anyhow!("Record a macro before playing it"),
+                    Tab::Macro,
+                )
+            }),
+            RunMode::Clicks => None,
+        };
+        if let Some((e, tab)) = checked {
+            error!("Can't start: {}", e);
+            self.clicker.last_error = Some(e.to_string());
+            self.tab = tab;
+            return;
+        }
+
+        let countdown = if self.use_countdown && (!from_hotkey || self.countdown_on_hotkey) {
+            Duration::from_secs(self.countdown_secs as u64)
+        } else {
+            Duration::ZERO
+        };
+        let config = match ClickerConfig::from_app(self, countdown) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Can't start: {}", e);
+                self.clicker.last_error = Some(e.to_string());
+                self.tab = Tab::Settings;
+                return;
+            }
+        };
+        info!("Starting clicker with jitter seed {}", config.seed());
+        self.clicker.last_seed = Some(config.seed());
 
-        let config = ClickerConfig::from_app(self);
+        let starts_at = Instant::now() + config.start_delay();
+        let stops_at = config.run_limit().map(|limit| starts_at + limit);
+        self.clicker.starts_at = Some(starts_at).filter(|_| !config.start_delay().is_zero());
+        self.clicker.stops_at = stops_at;
+        self.clicker.click_limit = config.click_limit();
+        self.stats.start(starts_at, config.target_interval());
+        self.tab = Tab::Statistics;
 
         let repaint_ctx = ctx.clone();
 
-        let handle = self.clicker.runtime.spawn(async move {
-            let mut jitter_gen = Jitter::new();
-            let mut click_count = 0u32;
-            let mut interval = tokio::time::interval(config.base_interval);
-            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-            interval.tick().await;
-            let mut next_tick = tokio::time::Instant::now();
-
-            loop {
-                tokio::select! {
-                    _ = wait_for_tick(&config, &mut jitter_gen, &mut interval, &mut next_tick) => {
-                        if config.use_num_clicks && click_count >= config.num_clicks {
-                            let _ = status_tx.send(ClickerStatus::Completed).await;
-                            repaint_ctx.request_repaint();
-                            break;
-                        }
+        self.clicker.run = Some(Clicker::start(
+            config,
+            input_handler,
+            self.clicker.runtime.handle(),
+            move || repaint_ctx.request_repaint(),
+        ));
+        self.clicker.is_running = true;
+    }
 
-                        let result = ClickApp::perform_click(&input_handler, &config);
+    fn toggle_profile_recording(&mut self) {
+        self.profile_error = None;
 
-                        if let Err(e) = result {
-                            let _ = status_tx.send(ClickerStatus::Error(e)).await;
-                            repaint_ctx.request_repaint();
-                            break;
-                        }
+        match self.profile_recorder.take() {
+            Some(recorder) => match recorder.finish() {
+                Ok(profile) => {
+                    self.learned_profile = Some(profile);
+                    self.use_learned_profile = true;
+                }
+                Err(e) => self.profile_error = Some(e.to_string()),
+            },
+            None => match ProfileRecorder::start() {
+                Ok(recorder) => self.profile_recorder = Some(recorder),
+                Err(e) => self.profile_error = Some(e.to_string()),
+            },
+        }
+    }
 
-                        click_count += 1;
-                    }
-                    _ = stop_rx.recv() => {
-                        repaint_ctx.request_repaint();
-                        break;
+    fn learned_profile_ui(&mut self, ui: &mut egui::Ui, unlocked: bool) {
+        ui.add_enabled(
+            unlocked,
+            egui::Checkbox::new(&mut self.use_learned_profile, "Learned timing:"),
+        )
+        .on_hover_text("Sample delays and hold times from a recording of your own clicking, scaled to the interval above");
+        ui.add_enabled_ui(unlocked, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(recorder) = &mut self.profile_recorder {
+                    let clicks = recorder.poll();
+                    if ui.button("Stop & learn").clicked() {
+                        self.toggle_profile_recording();
                     }
+                    ui.label(format!("{clicks} click(s) recorded"));
+                    ui.ctx().request_repaint_after(Duration::from_millis(100));
+                    return;
                 }
-            }
-        });
 
-        self.clicker.stop_sender = Some(stop_tx);
-        self.clicker.status_receiver = Some(status_rx);
-        self.clicker.task_handle = Some(handle);
-        self.clicker.is_running = true;
+                if ui
+                    .button("Record")
+                    .on_hover_text("Click at your natural pace, then stop the recording")
+                    .clicked()
+                {
+                    self.toggle_profile_recording();
+                }
+
+                if let Some(error) = &self.profile_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                } else if let Some(profile) = &self.learned_profile {
+                    ui.label(format!(
+                        "{:.1} CPS, {} samples",
+                        1000.0 / profile.mean_interval_ms,
+                        profile.samples
+                    ))
+                    .on_hover_text(format!(
+                        "Autocorrelation: {:.2}\nHold: {}",
+                        profile.autocorrelation,
+                        profile
+                            .hold_mean_ms
+                            .map(|ms| format!("{ms:.0} ± {:.0} ms", profile.hold_std_ms))
+                            .unwrap_or_else(|| "not recorded".to_owned())
+                    ));
+                }
+            })
+        });
+        ui.end_row();
     }
 
-    fn stop_clicker(&mut self) {
-        if !self.clicker.is_running {
-            return;
+    fn start_macro_recording(&mut self) {
+        self.macro_error = None;
+        match MacroRecorder::start() {
+            Ok(recorder) => self.macro_recorder = Some(recorder),
+            Err(e) => self.macro_error = Some(e.to_string()),
         }
+    }
 
-        if let Some(sender) = self.clicker.stop_sender.take() {
-            let _ = sender.try_send(());
+    /// Keeps the recording, leaving out the click on the Stop button when
+    /// `from_button` is set
+    fn stop_macro_recording(&mut self, from_button: bool) {
+        if let Some(recorder) = self.macro_recorder.take() {
+            self.recorded_macro = recorder.finish(from_button);
         }
-
-        self.clicker.task_handle = None;
-        self.clicker.status_receiver = None;
-        self.clicker.is_running = false;
     }
-}
 
-impl Drop for ClickApp {
-    fn drop(&mut self) {
-        if let Some(handle) = self.clicker.task_handle.take() {
-            handle.abort();
+    /// Pulls in recorded input, stopping when the stop hotkey is pressed even
+    /// while another window has focus
+    fn poll_macro_recording(&mut self, ctx: &egui::Context) {
+        let Some(recorder) = &mut self.macro_recorder else {
+            return;
+        };
+
+        let stop_key = keys::from_egui(self.stop_record_shortcut.logical_key);
+        if recorder.poll(stop_key) {
+            self.stop_macro_recording(false);
         }
+        ctx.request_repaint_after(Duration::from_millis(100));
     }
-}
 
-impl eframe::App for ClickApp {
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+    /// Reads the point and color under the pointer once a pick's countdown ends
+    fn poll_color_pick(&mut self, ctx: &egui::Context) {
+        let Some((at, target)) = self.color_pick else {
+            return;
+        };
+        if at > Instant::now() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+            return;
+        }
+        self.color_pick = None;
+
+        let picked = Screen::connect().and_then(|screen| {
+            let location = screen.pointer()?;
+            Ok((location, screen.pixel(location)?))
+        });
+        let (location, color) = match picked {
+            Ok(picked) => picked,
+            Err(e) => {
+                error!("Color pick failed: {}", e);
+                self.screen_error = Some(e.to_string());
+                return;
+            }
+        };
+        self.screen_error = None;
+
+        let condition = match target {
+            PickTarget::Settings => Some(&mut self.pixel_condition),
+            // The step may have been moved or removed during the countdown
+            PickTarget::Step(index) => match self.sequence.steps.get_mut(index) {
+                Some(Step::WaitColor { condition }) => Some(condition),
+                _ => None,
+            },
+        };
+        if let Some(condition) = condition {
+            condition.location = location;
+            condition.color = color;
+        }
     }
 
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let was_running = self.clicker.is_running;
+    fn open_target_image(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .pick_file()
+        else {
+            return;
+        };
 
-        // Check for status updates
-        if let Some(receiver) = &mut self.clicker.status_receiver {
-            let mut last_status = None;
-            while let Ok(status) = receiver.try_recv() {
-                last_status = Some(status);
-            }
-
-            if let Some(status) = last_status {
-                match status {
-                    ClickerStatus::Completed => self.stop_clicker(),
-                    ClickerStatus::Error(e) => {
-                        error!("Clicker error: {}", e);
-                        self.stop_clicker();
-                    }
-                }
+        match Template::load(&path) {
+            Ok(_) => {
+                self.image_target.path = Some(path);
+                self.screen_error = None;
             }
+            Err(e) => self.screen_error = Some(e.to_string()),
         }
+    }
 
-        // Handle keyboard shortcuts
-        ctx.input_mut(|i| {
-            if i.consume_shortcut(&self.start_shortcut) && !self.clicker.is_running {
-                self.start_clicker(ctx);
+    fn macro_ui(&mut self, ui: &mut egui::Ui) {
+        let unlocked = !self.clicker.is_running;
+
+        egui::Grid::new("macro_grid")
+            .num_columns(2)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("Recording:");
+                ui.horizontal(|ui| {
+                    if let Some(recorder) = &self.macro_recorder {
+                        let count = recorder.event_count();
+                        if ui
+                            .button(format!(
+                                "Stop ({})",
+                                ui.ctx().format_shortcut(&self.stop_record_shortcut)
+                            ))
+                            .clicked()
+                        {
+                            self.stop_macro_recording(true);
+                        }
+                        ui.label(format!("{count} event(s)"));
+                        return;
+                    }
+
+                    if ui
+                        .add_enabled(
+                            !self.clicker.is_running,
+                            egui::Button::new(format!(
+                                "Record ({})",
+                                ui.ctx().format_shortcut(&self.record_shortcut)
+                            )),
+                        )
+                        .on_hover_text(
+                            "Records mouse and keyboard input from every window until stopped",
+                        )
+                        .clicked()
+                    {
+                        self.start_macro_recording();
+                    }
+                    if let Some(error) = &self.macro_error {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Macro:");
+                ui.horizontal(|ui| {
+                    if self.recorded_macro.is_empty() {
+                        ui.label("Nothing recorded");
+                        return;
+                    }
+
+                    ui.label(format!(
+                        "{} event(s), {:.1} s",
+                        self.recorded_macro.events.len(),
+                        self.recorded_macro.duration().as_secs_f64()
+                    ));
+                    if ui
+                        .add_enabled(self.macro_recorder.is_none(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        self.recorded_macro = Macro::default();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("File:");
+                ui.horizontal(|ui| {
+                    let idle = unlocked && self.macro_recorder.is_none();
+                    if ui.add_enabled(idle, egui::Button::new("Open…")).clicked() {
+                        self.open_macro();
+                    }
+                    if ui
+                        .add_enabled(
+                            idle && !self.recorded_macro.is_empty(),
+                            egui::Button::new("Save…"),
+                        )
+                        .clicked()
+                    {
+                        self.save_macro();
+                    }
+                });
+                ui.end_row();
+
+                ui.label("evemu:");
+                ui.horizontal(|ui| {
+                    let idle = unlocked && self.macro_recorder.is_none();
+                    let screen = screen_size(ui.ctx());
+                    if ui
+                        .add_enabled(idle, egui::Button::new("Import…"))
+                        .on_hover_text("Loads a recording made with evemu-record")
+                        .clicked()
+                    {
+                        self.import_evemu(screen);
+                    }
+                    if ui
+                        .add_enabled(
+                            idle && !self.recorded_macro.is_empty(),
+                            egui::Button::new("Export…"),
+                        )
+                        .on_hover_text("Saves the macro for evemu-play and other evemu tools")
+                        .clicked()
+                    {
+                        self.export_evemu(screen);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Speed:");
+                ui.add_enabled(
+                    unlocked,
+                    egui::DragValue::new(&mut self.macro_speed)
+                        .speed(0.05)
+                        .range(macros::MIN_SPEED..=macros::MAX_SPEED)
+                        .max_decimals(2)
+                        .suffix("×"),
+                );
+                ui.end_row();
+
+                ui.add_enabled(
+                    unlocked,
+                    egui::Checkbox::new(&mut self.use_macro_loops, "Loops:"),
+                )
+                .on_hover_text("Loops forever when unchecked");
+                ui.add_enabled(
+                    unlocked && self.use_macro_loops,
+                    egui::DragValue::new(&mut self.macro_loops)
+                        .speed(0.1)
+                        .range(1..=u32::MAX),
+                );
+                ui.end_row();
+            });
+
+        ui.add_space(6.0);
+        ui.label(
+            "Set the action to \"Play macro\" in Settings to replay it. \
+             Random delay, the click limit and the schedule still apply.",
+        );
+    }
+
+    fn open_macro(&mut self) {
+        match files::open_dialog(
+            "Click macro",
+            &[files::MACRO_EXTENSION],
+            files::macro_from_str,
+        ) {
+            Ok(Some(recording)) => {
+                self.recorded_macro = recording;
+                self.macro_error = None;
+            }
+            Ok(None) => {}
+            Err(e) => self.macro_error = Some(e.to_string()),
+        }
+    }
+
+    fn save_macro(&mut self) {
+        let saved = files::macro_to_string(&self.recorded_macro).and_then(|text| {
+            files::save_dialog("Click macro", files::MACRO_EXTENSION, "macro", &text)
+        });
+        match saved {
+            Ok(_) => self.macro_error = None,
+            Err(e) => self.macro_error = Some(e.to_string()),
+        }
+    }
+
+    fn import_evemu(&mut self, screen: Option<ScreenSize>) {
+        let imported = files::open_dialog("evemu recording", &[evemu::EXTENSION, "txt"], |text| {
+            evemu::import(text, screen)
+        });
+        match imported {
+            Ok(Some(recording)) => {
+                self.recorded_macro = recording;
+                self.macro_error = None;
+            }
+            Ok(None) => {}
+            Err(e) => self.macro_error = Some(e.to_string()),
+        }
+    }
+
+    fn export_evemu(&mut self, screen: Option<ScreenSize>) {
+        let text = evemu::export(&self.recorded_macro, screen);
+        match files::save_dialog("evemu recording", evemu::EXTENSION, "macro", &text) {
+            Ok(_) => self.macro_error = None,
+            Err(e) => self.macro_error = Some(e.to_string()),
+        }
+    }
+
+    fn open_sequence(&mut self) {
+        match files::open_dialog(
+            "Click sequence",
+            &[files::SEQUENCE_EXTENSION],
+            files::sequence_from_str,
+        ) {
+            Ok(Some(sequence)) => {
+                self.sequence = sequence;
+                self.sequence_error = None;
+            }
+            Ok(None) => {}
+            Err(e) => self.sequence_error = Some(e.to_string()),
+        }
+    }
+
+    fn save_sequence(&mut self) {
+        let saved = files::sequence_to_string(&self.sequence).and_then(|text| {
+            files::save_dialog(
+                "Click sequence",
+                files::SEQUENCE_EXTENSION,
+                "sequence",
+                &text,
+            )
+        });
+        match saved {
+            Ok(_) => self.sequence_error = None,
+            Err(e) => self.sequence_error = Some(e.to_string()),
+        }
+    }
+
+    fn sequence_ui(&mut self, ui: &mut egui::Ui) {
+        let unlocked = !self.clicker.is_running;
+
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(unlocked, |ui| {
+                ui.menu_button("Add step", |ui| {
+                    for kind in StepKind::all() {
+                        if ui.button(kind.to_string()).clicked() {
+                            self.sequence.steps.push(kind.to_step());
+                        }
+                    }
+                });
+            });
+
+            if ui
+                .add_enabled(unlocked, egui::Button::new("Open…"))
+                .clicked()
+            {
+                self.open_sequence();
+            }
+            if ui.button("Save…").clicked() {
+                self.save_sequence();
+            }
+
+            if let Some(error) = &self.sequence_error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            } else if let Err(e) = self.sequence.compile() {
+                ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+            } else if self.run_mode != RunMode::Sequence {
+                ui.label("Set the action to \"Run sequence\" in Settings to use it");
+            }
+        });
+        ui.add_space(6.0);
+
+        let labels: Vec<String> = self
+            .sequence
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Label { name } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut moved = None;
+        let mut removed = None;
+        let mut pick = None;
+        let mut depth = 0usize;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.add_enabled_ui(unlocked, |ui| {
+                for (index, step) in self.sequence.steps.iter_mut().enumerate() {
+                    if matches!(step, Step::EndRepeat) {
+                        depth = depth.saturating_sub(1);
+                    }
+
+                    let row = ui.horizontal(|ui| {
+                        ui.dnd_drag_source(ui.id().with(("step", index)), index, |ui| {
+                            ui.label("☰");
+                        })
+                        .response
+                        .on_hover_cursor(egui::CursorIcon::Grab);
+                        ui.add_space(depth as f32 * 12.0);
+                        ui.label(format!("{}. {}", index + 1, step.kind()));
+                        let picking = pick_countdown(self.color_pick, PickTarget::Step(index));
+                        if step_editor(ui, index, step, &labels, picking) {
+                            pick = Some(PickTarget::Step(index));
+                        }
+                        if ui.small_button("✖").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+
+                    if let Some(from) = row.response.dnd_hover_payload::<usize>()
+                        && *from != index
+                    {
+                        // Show where the dragged step will land
+                        let rect = row.response.rect;
+                        let y = if *from < index {
+                            rect.bottom()
+                        } else {
+                            rect.top()
+                        };
+                        ui.painter()
+                            .hline(rect.x_range(), y, ui.visuals().selection.stroke);
+                    }
+                    if let Some(from) = row.response.dnd_release_payload::<usize>() {
+                        moved = Some((*from, index));
+                    }
+
+                    if matches!(step, Step::Repeat { .. }) {
+                        depth += 1;
+                    }
+                }
+            });
+        });
+
+        if let Some((from, to)) = moved {
+            let step = self.sequence.steps.remove(from);
+            self.sequence.steps.insert(to, step);
+        }
+        if let Some(index) = removed {
+            self.sequence.steps.remove(index);
+        }
+        if let Some(target) = pick {
+            self.color_pick = Some((Instant::now() + PICK_DELAY, target));
+        }
+    }
+
+    fn script_ui(&mut self, ui: &mut egui::Ui) {
+        let unlocked = !self.clicker.is_running;
+
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                unlocked,
+                egui::Checkbox::new(&mut self.use_script_runs, "Runs:"),
+            )
+            .on_hover_text("Runs until stopped when unchecked");
+            ui.add_enabled(
+                unlocked && self.use_script_runs,
+                egui::DragValue::new(&mut self.script_runs)
+                    .speed(0.1)
+                    .range(1..=u32::MAX),
+            );
+
+            if let Err(e) = Script::compile(&self.script) {
+                ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+            } else if self.run_mode != RunMode::Script {
+                ui.label("Set the action to \"Run script\" in Settings to use it");
+            }
+        });
+        ui.add_space(6.0);
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.add(
+                egui::TextEdit::multiline(&mut self.script)
+                    .code_editor()
+                    .interactive(unlocked)
+                    .desired_rows(10)
+                    .desired_width(f32::INFINITY),
+            );
+
+            ui.add_space(6.0);
+            egui::CollapsingHeader::new("Reference").show(ui, |ui| {
+                ui.label(egui::RichText::new(script::REFERENCE).monospace());
+            });
+        });
+    }
+
+    fn rules_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(
+            &mut self.use_app_rules,
+            "Switch profiles with the focused window (X11)",
+        )
+        .on_hover_text("Clicking also pauses while no window with a rule has focus");
+        if let Some(error) = &self.focus_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        ui.add_space(6.0);
+
+        let profile_names: Vec<String> = self
+            .profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect();
+        let default_profile = profile_names[self.active_profile].clone();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let mut remove = None;
+            let mut swap = None;
+            let rule_count = self.app_rules.len();
+            egui::Grid::new("app_rules")
+                .num_columns(4)
+                .spacing([10.0, 4.0])
+                .show(ui, |ui| {
+                    for (index, rule) in self.app_rules.iter_mut().enumerate() {
+                        egui::ComboBox::from_id_salt(("rule_field", index))
+                            .selected_text(rule.field.to_string())
+                            .show_ui(ui, |ui| {
+                                for field in RuleField::all() {
+                                    ui.selectable_value(&mut rule.field, field, field.to_string());
+                                }
+                            });
+                        ui.add(
+                            egui::TextEdit::singleline(&mut rule.pattern)
+                                .hint_text("contains…")
+                                .desired_width(160.0),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.label("→");
+                            egui::ComboBox::from_id_salt(("rule_profile", index))
+                                .selected_text(&rule.profile)
+                                .show_ui(ui, |ui| {
+                                    for name in &profile_names {
+                                        ui.selectable_value(&mut rule.profile, name.clone(), name);
+                                    }
+                                });
+                            if !profile_names.contains(&rule.profile) {
+                                ui.colored_label(ui.visuals().error_fg_color, "No such profile");
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.add_enabled(index > 0, egui::Button::new("⏶")).clicked() {
+                                swap = Some(index - 1);
+                            }
+                            if ui
+                                .add_enabled(index + 1 < rule_count, egui::Button::new("⏷"))
+                                .clicked()
+                            {
+                                swap = Some(index);
+                            }
+                            if ui.button("🗑").clicked() {
+                                remove = Some(index);
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+            if let Some(index) = swap {
+                self.app_rules.swap(index, index + 1);
+            }
+            if let Some(index) = remove {
+                self.app_rules.remove(index);
+            }
+
+            if ui.button("Add rule").clicked() {
+                self.app_rules.push(AppRule {
+                    profile: default_profile.clone(),
+                    ..AppRule::default()
+                });
+            }
+
+            ui.add_space(12.0);
+            ui.label("Recently focused:");
+            if self.recent_windows.is_empty() {
+                ui.weak("Nothing yet. Switch to the window you want a rule for.");
+            }
+            for window in &self.recent_windows {
+                ui.horizontal(|ui| {
+                    if ui.button("Add rule").clicked() {
+                        self.app_rules
+                            .push(AppRule::for_window(window, default_profile.clone()));
+                    }
+                    ui.label(format!("{} · {}", window.class, window.title));
+                });
+            }
+        });
+    }
+
+    /// Describes the state of a running clicker: paused, clicks left and the
+    /// next scheduled start or stop
+    fn status_text(&self) -> Option<String> {
+        // Countdowns freeze while paused
+        let now = self.clicker.paused_at.unwrap_or_else(Instant::now);
+
+        if let Some(remaining) = self.start_countdown(now) {
+            return Some(format!("Starts in {remaining}"));
+        }
+
+        let mut parts = Vec::new();
+        if self.focus_paused {
+            parts.push("Paused until a window with a rule has focus".to_owned());
+        } else if self.clicker.paused_at.is_some() {
+            parts.push("Paused".to_owned());
+        }
+        if let Some(limit) = self.clicker.click_limit {
+            let remaining = (limit as u64).saturating_sub(self.stats.clicks());
+            parts.push(format!("{remaining} click(s) left"));
+        }
+        if let Some(stops_at) = self.clicker.stops_at {
+            parts.push(format!(
+                "Stops in {}",
+                format_countdown(stops_at.saturating_duration_since(now))
+            ));
+        }
+
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+
+    /// Time left before the first click, if the clicker is still waiting to start
+    fn start_countdown(&self, now: Instant) -> Option<String> {
+        let remaining = self
+            .clicker
+            .starts_at
+            .filter(|at| *at > now)
+            .map(|at| at - now)?;
+
+        Some(if remaining < Duration::from_secs(60) {
+            format!("{}s", remaining.as_secs_f64().ceil())
+        } else {
+            format_countdown(remaining)
+        })
+    }
+
+    /// Shows the start countdown in the window title, which stays visible while
+    /// the window is behind the target
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let title = match self.start_countdown(Instant::now()) {
+            _ if self.macro_recorder.is_some() => format!("{WINDOW_TITLE} - Recording"),
+            Some(remaining) if self.clicker.is_running => {
+                format!("{WINDOW_TITLE} - Starts in {remaining}")
+            }
+            _ => WINDOW_TITLE.to_owned(),
+        };
+
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    /// Whether the run mode has something to run
+    fn can_start(&self) -> bool {
+        self.run_mode != RunMode::Macro || !self.recorded_macro.is_empty()
+    }
+
+    /// Whether the clicker has started clicking and so can be paused
+    fn can_pause(&self) -> bool {
+        self.clicker.is_running
+            && self
+                .clicker
+                .starts_at
+                .is_none_or(|starts_at| starts_at <= Instant::now())
+    }
+
+    /// Sends edited live settings to the running clicker, which applies them on its next tick
+    fn push_live_settings(&mut self) {
+        let Some(run) = &self.clicker.run else {
+            return;
+        };
+
+        // Settings the clicker refuses leave it as it was; the timing preview says why
+        if let Ok(true) = run.update(LiveSettings::from_app(self))
+            && let Some(target) = ClickerConfig::from_app(self, Duration::ZERO)
+                .ok()
+                .and_then(|config| config.target_interval())
+        {
+            self.stats.set_target_interval(target);
+        }
+    }
+
+    /// Changes the click rate by `delta` CPS, switching to CPS mode if needed
+    fn adjust_cps(&mut self, delta: f64) {
+        let current = 1.0 / self.calculate_interval().as_secs_f64();
+        self.cps = (current + delta).clamp(MIN_CPS, MAX_CPS);
+        self.interval_mode = IntervalMode::Cps;
+    }
+
+    /// Starts or stops following focus as rules are switched on and off, and
+    /// applies the first matching rule when the focused window changes
+    fn poll_focus(&mut self, ctx: &egui::Context) {
+        if !self.use_app_rules {
+            self.focus = None;
+            self.focused = None;
+            self.focus_error = None;
+            return;
+        }
+        if self.focus.is_none() && self.focus_error.is_none() {
+            let ctx = ctx.clone();
+            match FocusWatcher::start(move || ctx.request_repaint()) {
+                Ok(watcher) => self.focus = Some(watcher),
+                Err(e) => self.focus_error = Some(e.to_string()),
+            }
+        }
+        let Some(watcher) = &self.focus else {
+            return;
+        };
+
+        match watcher.changed() {
+            Ok(Some(focused)) => {
+                if let Some(window) = &focused {
+                    self.recent_windows
+                        .retain(|recent| recent.class != window.class);
+                    self.recent_windows.insert(0, window.clone());
+                    self.recent_windows.truncate(RECENT_WINDOWS);
+                }
+                self.focused = focused;
+
+                // Only on a change, so the profile can still be picked by hand
+                if let Some(rule) = self.focused_rule()
+                    && let Some(index) = self
+                        .profiles
+                        .iter()
+                        .position(|profile| profile.name == rule.profile)
+                {
+                    self.switch_profile(index);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                self.focus = None;
+                self.focused = None;
+                self.focus_error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// The first rule matching the focused window, skipping rules for a
+    /// profile that no longer exists
+    fn focused_rule(&self) -> Option<&AppRule> {
+        let window = self.focused.as_ref()?;
+        self.app_rules.iter().find(|rule| {
+            rule.matches(window)
+                && self
+                    .profiles
+                    .iter()
+                    .any(|profile| profile.name == rule.profile)
+        })
+    }
+
+    /// Pauses clicking while focus is away from every window with a rule, and
+    /// resumes once it's back unless the pause was made by hand
+    fn pause_for_focus(&mut self) {
+        if !self.clicker.is_running || self.focus.is_none() {
+            self.focus_paused = false;
+            return;
+        }
+
+        let in_target = self.focused_rule().is_some();
+        let paused = self.clicker.paused_at.is_some();
+        if !in_target && !paused && self.can_pause() {
+            self.toggle_pause();
+            self.focus_paused = self.clicker.paused_at.is_some();
+        } else if in_target && paused && self.focus_paused {
+            self.toggle_pause();
+            self.focus_paused = false;
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        let Some(run) = &self.clicker.run else {
+            return;
+        };
+
+        match self.clicker.paused_at.take() {
+            Some(paused_at) => {
+                if run.resume() {
+                    let paused_for = paused_at.elapsed();
+                    if let Some(stops_at) = &mut self.clicker.stops_at {
+                        *stops_at += paused_for;
+                    }
+                    self.stats.resume();
+                } else {
+                    self.clicker.paused_at = Some(paused_at);
+                }
+            }
+            None => {
+                if run.pause() {
+                    self.clicker.paused_at = Some(Instant::now());
+                    self.stats.pause();
+                }
+            }
+        }
+    }
+
+    fn drain_samples(&mut self) {
+        if let Some(run) = &mut self.clicker.run {
+            while let Some(event) = run.try_event() {
+                if let Event::Clicked(sample) = event {
+                    self.stats.record(sample);
+                }
+            }
+        }
+    }
+
+    fn stop_clicker(&mut self) {
+        if !self.clicker.is_running {
+            return;
+        }
+
+        if let Some(run) = &self.clicker.run {
+            run.stop();
+        }
+
+        self.drain_samples();
+        self.stats.stop();
+
+        self.clicker.run = None;
+        self.clicker.is_running = false;
+        self.clicker.paused_at = None;
+        self.clicker.click_limit = None;
+        self.clicker.starts_at = None;
+        self.clicker.stops_at = None;
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        // Settings that can't be changed on the fly are locked while running
+        let unlocked = !self.clicker.is_running;
+
+        self.profile_ui(ui, unlocked);
+
+        ui.add(egui::Separator::default().spacing(18.0));
+
+        // Interval section
+        ui.heading("Interval");
+        ui.add_space(6.0);
+
+        egui::Grid::new("interval_grid")
+            .num_columns(2)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.radio_value(&mut self.interval_mode, IntervalMode::Time, "Time:");
+                ui.add_enabled_ui(self.interval_mode == IntervalMode::Time, |ui| {
+                    widgets::time_interval_editor(ui, &mut self.time_interval);
+                });
+                ui.end_row();
+
+                ui.radio_value(&mut self.interval_mode, IntervalMode::Cps, "Target CPS:");
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(self.interval_mode == IntervalMode::Cps, |ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.cps)
+                                .speed(0.1)
+                                .range(MIN_CPS..=MAX_CPS)
+                                .max_decimals(2),
+                        );
+                    });
+                    ui.label("Step:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.cps_step)
+                            .speed(0.1)
+                            .range(MIN_CPS..=MAX_CPS)
+                            .max_decimals(2),
+                    )
+                    .on_hover_text(format!(
+                        "CPS change for the slower ({}) and faster ({}) hotkeys while running",
+                        ui.ctx().format_shortcut(&self.slower_shortcut),
+                        ui.ctx().format_shortcut(&self.faster_shortcut)
+                    ));
+                });
+                ui.end_row();
+
+                ui.add_enabled(
+                    unlocked,
+                    egui::Checkbox::new(&mut self.use_ramp, "Ramp up:"),
+                )
+                .on_hover_text("Ease the click rate from a starting CPS up to the target");
+                ui.add_enabled_ui(unlocked && self.use_ramp, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("From");
+                        ui.add(
+                            egui::DragValue::new(&mut self.ramp.start_cps)
+                                .speed(0.1)
+                                .range(MIN_CPS..=MAX_CPS)
+                                .max_decimals(2),
+                        );
+                        ui.label("CPS");
+                        egui::ComboBox::from_id_salt("ramp_easing")
+                            .selected_text(self.ramp.easing.to_string())
+                            .show_ui(ui, |ui| {
+                                for variant in Easing::all() {
+                                    ui.selectable_value(
+                                        &mut self.ramp.easing,
+                                        variant,
+                                        variant.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                });
+                ui.end_row();
+
+                ui.add_enabled(unlocked && self.use_ramp, egui::Label::new("Ramp time:"));
+                ui.add_enabled_ui(unlocked && self.use_ramp, |ui| {
+                    widgets::time_interval_editor(ui, &mut self.ramp.duration);
+                });
+                ui.end_row();
+
+                ui.add_enabled_ui(unlocked && self.use_ramp, |ui| {
+                    ui.checkbox(&mut self.ramp.use_ramp_down, "Ramp down:")
+                        .on_hover_text("Slow back down before the click limit is reached");
+                });
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(unlocked && self.use_ramp && self.ramp.use_ramp_down, |ui| {
+                        ui.label("Over the last");
+                        ui.add(
+                            egui::DragValue::new(&mut self.ramp.ramp_down_clicks)
+                                .speed(0.1)
+                                .range(1..=u32::MAX),
+                        );
+                        ui.label("click(s)");
+                    });
+                });
+                ui.end_row();
+            });
+
+        ui.add(egui::Separator::default().spacing(18.0));
+
+        // Behavior section
+        ui.heading("Behavior");
+        ui.add_space(6.0);
+
+        egui::Grid::new("behavior_grid")
+            .num_columns(2)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("Action:");
+                ui.add_enabled_ui(unlocked, |ui| {
+                    egui::ComboBox::from_id_salt("run_mode")
+                        .selected_text(self.run_mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for variant in RunMode::all() {
+                                ui.selectable_value(
+                                    &mut self.run_mode,
+                                    variant,
+                                    variant.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.end_row();
+
+                ui.label("Mouse button:");
+                egui::ComboBox::from_id_salt("mouse_button")
+                    .selected_text(self.mouse_button.to_string())
+                    .show_ui(ui, |ui| {
+                        for variant in MouseButton::all() {
+                            ui.selectable_value(
+                                &mut self.mouse_button,
+                                variant,
+                                variant.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Click type:");
+                egui::ComboBox::from_id_salt("click_type")
+                    .selected_text(self.click_type.to_string())
+                    .show_ui(ui, |ui| {
+                        for variant in ClickAction::all() {
+                            ui.selectable_value(&mut self.click_type, variant, variant.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.add_enabled(
+                    unlocked,
+                    egui::Checkbox::new(&mut self.use_num_clicks, "Repeat only:"),
+                );
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(unlocked && self.use_num_clicks, |ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.num_clicks)
+                                .speed(1.0)
+                                .range(1..=u32::MAX),
+                        );
+                    });
+                    ui.label("click(s)");
+                });
+                ui.end_row();
+
+                ui.add_enabled(unlocked, egui::Checkbox::new(&mut self.use_burst, "Burst:"));
+                ui.add_enabled_ui(unlocked && self.use_burst, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.burst.clicks)
+                                .speed(0.1)
+                                .range(1..=u32::MAX),
+                        );
+                        ui.label("click(s), then rest");
+                    });
+                });
+                ui.end_row();
+
+                ui.add_enabled(
+                    unlocked && self.use_burst,
+                    egui::Label::new("Burst interval:"),
+                );
+                ui.add_enabled_ui(unlocked && self.use_burst, |ui| {
+                    widgets::time_interval_editor(ui, &mut self.burst.interval);
+                });
+                ui.end_row();
+
+                ui.add_enabled(unlocked && self.use_burst, egui::Label::new("Rest:"));
+                ui.add_enabled_ui(unlocked && self.use_burst, |ui| {
+                    widgets::time_interval_editor(ui, &mut self.burst.rest);
+                });
+                ui.end_row();
+
+                ui.add_enabled_ui(unlocked && self.use_burst, |ui| {
+                    ui.checkbox(&mut self.use_num_bursts, "Bursts only:");
+                });
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(unlocked && self.use_burst && self.use_num_bursts, |ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.num_bursts)
+                                .speed(0.1)
+                                .range(1..=u32::MAX),
+                        );
+                    });
+                    ui.label("burst(s)");
+                });
+                ui.end_row();
+            });
+
+        ui.add(egui::Separator::default().spacing(18.0));
+
+        // Schedule section
+        ui.heading("Schedule");
+        ui.add_space(6.0);
+
+        ui.add_enabled_ui(unlocked, |ui| {
+            egui::Grid::new("schedule_grid")
+                .num_columns(2)
+                .spacing([10.0, 4.0])
+                .show(ui, |ui| {
+                    ui.checkbox(&mut self.use_run_duration, "Stop after:");
+                    ui.add_enabled_ui(self.use_run_duration, |ui| {
+                        widgets::time_interval_editor(ui, &mut self.run_duration);
+                    });
+                    ui.end_row();
+
+                    ui.checkbox(&mut self.use_stop_at, "Stop at:");
+                    ui.add_enabled_ui(self.use_stop_at, |ui| {
+                        widgets::time_of_day_editor(ui, &mut self.stop_at);
+                    });
+                    ui.end_row();
+
+                    ui.checkbox(&mut self.use_start_at, "Start at:");
+                    ui.add_enabled_ui(self.use_start_at, |ui| {
+                        widgets::time_of_day_editor(ui, &mut self.start_at);
+                    });
+                    ui.end_row();
+
+                    ui.checkbox(&mut self.use_countdown, "Countdown:")
+                        .on_hover_text(
+                            "Wait before the first click, so the cursor can leave the Start button",
+                        );
+                    ui.add_enabled_ui(self.use_countdown, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut self.countdown_secs)
+                                    .speed(0.1)
+                                    .range(1..=60)
+                                    .suffix(" s"),
+                            );
+                            ui.checkbox(&mut self.countdown_on_hotkey, "Also for hotkey");
+                        });
+                    });
+                    ui.end_row();
+                });
+        });
+
+        ui.add(egui::Separator::default().spacing(18.0));
+
+        // Extra section
+        ui.heading("Extra");
+        ui.add_space(6.0);
+
+        egui::Grid::new("extra_grid")
+            .num_columns(2)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.checkbox(&mut self.use_jitter, "Random delay:");
+                ui.add_enabled_ui(self.use_jitter, |ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.jitter)
+                            .speed(0.1)
+                            .range(0..=1000)
+                            .prefix("± ")
+                            .suffix(" ms"),
+                    );
+                });
+                ui.end_row();
+
+                ui.add_enabled(unlocked, egui::Checkbox::new(&mut self.use_seed, "Seed:"));
+                ui.add_enabled_ui(unlocked, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(self.use_jitter && self.use_seed, |ui| {
+                            ui.add(egui::DragValue::new(&mut self.seed).speed(1.0));
+                        });
+                        if let Some(last_seed) = self.clicker.last_seed
+                            && ui
+                                .small_button("Reuse last")
+                                .on_hover_text(format!("Last run used seed {last_seed}"))
+                                .clicked()
+                        {
+                            self.seed = last_seed;
+                            self.use_seed = true;
+                        }
+                    });
+                });
+                ui.end_row();
+
+                self.learned_profile_ui(ui, unlocked);
+
+                ui.checkbox(&mut self.use_location, "Location:");
+                ui.add_enabled_ui(self.use_location, |ui| {
+                    widgets::coordinates_editor(ui, &mut self.location);
+                });
+                ui.end_row();
+
+                ui.add_enabled(
+                    unlocked,
+                    egui::Checkbox::new(&mut self.use_pixel_condition, "Only on color:"),
+                )
+                .on_hover_text("Skips clicks while the point doesn't have the color");
+                ui.add_enabled_ui(unlocked && self.use_pixel_condition, |ui| {
+                    let picking = pick_countdown(self.color_pick, PickTarget::Settings);
+                    if widgets::pixel_condition_editor(ui, &mut self.pixel_condition, picking) {
+                        self.color_pick = Some((Instant::now() + PICK_DELAY, PickTarget::Settings));
+                    }
+                });
+                ui.end_row();
+
+                let targeting = unlocked && self.use_image_target;
+                ui.add_enabled(
+                    unlocked,
+                    egui::Checkbox::new(&mut self.use_image_target, "Target image:"),
+                )
+                .on_hover_text("Clicks where the image shows up on screen instead of at Location");
+                ui.add_enabled_ui(targeting, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Open…").clicked() {
+                            self.open_target_image();
+                        }
+                        match &self.image_target.path {
+                            Some(path) => {
+                                let name = path.file_name().unwrap_or_default();
+                                ui.label(name.to_string_lossy())
+                                    .on_hover_text(path.display().to_string());
+                            }
+                            None => {
+                                ui.label("No image chosen");
+                            }
+                        }
+                    });
+                });
+                ui.end_row();
+
+                ui.add_enabled(targeting, egui::Label::new("Match:"));
+                ui.add_enabled_ui(targeting, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut self.image_target.confidence)
+                                .speed(0.005)
+                                .range(0.5..=1.0)
+                                .fixed_decimals(2),
+                        )
+                        .on_hover_text("How similar the screen has to be to the image");
+                        ui.label("Scatter:");
+                        ui.add(
+                            egui::DragValue::new(&mut self.image_target.scatter)
+                                .speed(0.1)
+                                .range(0..=200)
+                                .prefix("± ")
+                                .suffix(" px"),
+                        );
+                    });
+                });
+                ui.end_row();
+
+                ui.add_enabled(targeting, egui::Label::new("Search:"));
+                ui.add_enabled_ui(targeting, |ui| {
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("target_search")
+                            .selected_text(self.image_target.search.to_string())
+                            .show_ui(ui, |ui| {
+                                for search in TargetSearch::all() {
+                                    let label = search.to_string();
+                                    ui.selectable_value(
+                                        &mut self.image_target.search,
+                                        search,
+                                        label,
+                                    );
+                                }
+                            });
+                        ui.label("If not found:");
+                        egui::ComboBox::from_id_salt("target_miss")
+                            .selected_text(self.image_target.on_miss.to_string())
+                            .show_ui(ui, |ui| {
+                                for action in MissAction::all() {
+                                    let label = action.to_string();
+                                    ui.selectable_value(
+                                        &mut self.image_target.on_miss,
+                                        action,
+                                        label,
+                                    );
+                                }
+                            });
+                    });
+                });
+                ui.end_row();
+            });
+
+        ui.add_space(6.0);
+        egui::CollapsingHeader::new("Timing preview").show(ui, |ui| {
+            self.timing_preview_ui(ui);
+        });
+    }
+
+    /// Simulates the delays a run with the current settings would produce, in milliseconds
+    fn simulate_delays(&self, count: usize) -> anyhow::Result<Vec<f64>> {
+        let config = ClickerConfig::timing(self).build()?;
+        Ok(config
+            .preview_delays(self.seed, count)
+            .into_iter()
+            .map(|delay| delay.as_secs_f64() * 1000.0)
+            .collect())
+    }
+
+    fn timing_preview_ui(&mut self, ui: &mut egui::Ui) {
+        let key = PreviewKey::from_app(self);
+        if self
+            .timing_preview
+            .as_ref()
+            .is_none_or(|preview| preview.key != key)
+        {
+            let delays = self
+                .simulate_delays(PREVIEW_CLICKS)
+                .map_err(|e| e.to_string());
+            self.timing_preview = Some(TimingPreview { key, delays });
+        }
+        let Some(preview) = &self.timing_preview else {
+            return;
+        };
+
+        let delays = match &preview.delays {
+            Ok(delays) => delays,
+            Err(e) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                return;
+            }
+        };
+        let (mean, std) = delays
+            .iter()
+            .fold((0.0, 0.0), |(sum, sum_sq), ms| (sum + ms, sum_sq + ms * ms));
+        let mean = mean / delays.len() as f64;
+        let std = (std / delays.len() as f64 - mean * mean).max(0.0).sqrt();
+
+        ui.label(format!(
+            "{PREVIEW_CLICKS} simulated clicks: {mean:.2} ± {std:.2} ms"
+        ));
+        let bins = histogram(delays.iter().copied(), 30);
+        widgets::interval_histogram(ui, "preview_histogram", &bins);
+        widgets::interval_timeline(
+            ui,
+            "preview_timeline",
+            &delays[..delays.len().min(PREVIEW_TIMELINE)],
+        );
+    }
+
+    fn statistics_ui(&mut self, ui: &mut egui::Ui) {
+        if !self.stats.has_data() {
+            ui.label("Start the clicker to see live statistics.");
+            return;
+        }
+
+        egui::Grid::new("statistics_grid")
+            .num_columns(2)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("Clicks:");
+                ui.label(self.stats.clicks().to_string());
+                ui.end_row();
+
+                ui.label("Elapsed:");
+                ui.label(format_countdown(self.stats.elapsed()));
+                ui.end_row();
+
+                ui.label("CPS:");
+                ui.label(match self.stats.target_cps() {
+                    Some(target) => {
+                        format!("{:.2} (target {target:.2})", self.stats.achieved_cps())
+                    }
+                    None => format!("{:.2}", self.stats.achieved_cps()),
+                });
+                ui.end_row();
+
+                ui.label("Interval:");
+                ui.label(match self.stats.interval_mean_std() {
+                    Some((mean, std)) => format!("{mean:.2} ± {std:.2} ms"),
+                    None => "–".to_owned(),
+                });
+                ui.end_row();
+
+                ui.label("Drift:")
+                    .on_hover_text("How late recent clicks were sent compared to their schedule");
+                ui.label(match self.stats.mean_drift_ms() {
+                    Some(drift) => format!("{drift:.2} ms"),
+                    None => "–".to_owned(),
+                });
+                ui.end_row();
+            });
+
+        ui.add_space(6.0);
+        let bins = histogram(self.stats.recent_intervals_ms(), 20);
+        widgets::interval_histogram(ui, "stats_histogram", &bins);
+
+        if self.clicker.is_running {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+    }
+}
+
+impl eframe::App for ClickApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        if let Some(text) = self.unreadable_settings.take() {
+            storage.set_string(UNREADABLE_KEY, text);
+        }
+        self.store_active_profile();
+        eframe::set_value(storage, eframe::APP_KEY, self);
+
+        // Leave a file that doesn't parse alone, rather than overwrite the user's edits
+        if self.config_error.is_none()
+            && let Some(mut config) = self.config.take()
+        {
+            if let Err(e) = config.write(self) {
+                error!("Couldn't save the config file: {e}");
+                self.config_error = Some(e.to_string());
+            }
+            self.config = Some(config);
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let was_running = self.clicker.is_running;
+
+        self.drain_samples();
+        self.poll_macro_recording(ctx);
+        self.poll_color_pick(ctx);
+        self.poll_config();
+        self.poll_focus(ctx);
+        self.pause_for_focus();
+
+        // Check for status updates
+        if let Some(Status::Ended(ending)) = self.clicker.run.as_ref().map(Clicker::status) {
+            match ending {
+                Ending::Completed | Ending::Stopped => self.stop_clicker(),
+                Ending::Failed(e) => {
+                    error!("Clicker error: {}", e);
+                    self.stop_clicker();
+                    self.clicker.last_error = Some(e);
+                }
+            }
+        }
+
+        // Handle keyboard shortcuts
+        ctx.input_mut(|i| {
+            if self.bind_profile_shortcut(i) {
+                return;
+            }
+            if i.consume_shortcut(&self.start_shortcut) && !self.clicker.is_running {
+                self.start_clicker(ctx, true);
             }
             if i.consume_shortcut(&self.stop_shortcut) && self.clicker.is_running {
                 self.stop_clicker();
                 ctx.request_repaint();
             }
+            if i.consume_shortcut(&self.pause_shortcut) && self.can_pause() {
+                self.toggle_pause();
+            }
+            if i.consume_shortcut(&self.faster_shortcut) && self.clicker.is_running {
+                self.adjust_cps(self.cps_step);
+            }
+            if i.consume_shortcut(&self.slower_shortcut) && self.clicker.is_running {
+                self.adjust_cps(-self.cps_step);
+            }
+            if i.consume_shortcut(&self.record_shortcut)
+                && self.macro_recorder.is_none()
+                && !self.clicker.is_running
+            {
+                self.start_macro_recording();
+            }
+            if i.consume_shortcut(&self.stop_record_shortcut) {
+                self.stop_macro_recording(false);
+            }
+            if !self.clicker.is_running
+                && self.macro_recorder.is_none()
+                && let Some(index) = self.profiles.iter().position(|profile| {
+                    profile
+                        .shortcut
+                        .is_some_and(|shortcut| i.consume_shortcut(&shortcut))
+                })
+            {
+                self.switch_profile(index);
+                if self.profiles[index].start_on_shortcut {
+                    self.start_clicker(ctx, true);
+                }
+            }
         });
 
         if was_running && !self.clicker.is_running {
@@ -304,19 +2508,41 @@ impl eframe::App for ClickApp {
                     .inner_margin(egui::Margin::same(12)),
             )
             .show(ctx, |ui| {
-                ui.columns_const(|[left, right]| {
-                    left.add_enabled_ui(!self.clicker.is_running, |ui| {
+                ui.columns_const(|[left, middle, right]| {
+                    let can_start = self.can_start();
+                    left.add_enabled_ui(!self.clicker.is_running && can_start, |ui| {
+                        let mut start = ui.add_sized(
+                            [0.0, 36.0],
+                            egui::Button::new(format!(
+                                "Start ({})",
+                                ctx.format_shortcut(&self.start_shortcut)
+                            )),
+                        );
+                        if !can_start {
+                            start = start.on_disabled_hover_text("Record a macro to play first");
+                        }
+                        if start.clicked() {
+                            self.start_clicker(ctx, false);
+                        }
+                    });
+
+                    middle.add_enabled_ui(self.can_pause(), |ui| {
+                        let label = if self.clicker.paused_at.is_some() {
+                            "Resume"
+                        } else {
+                            "Pause"
+                        };
                         if ui
                             .add_sized(
                                 [0.0, 36.0],
                                 egui::Button::new(format!(
-                                    "Start ({})",
-                                    ctx.format_shortcut(&self.start_shortcut)
+                                    "{label} ({})",
+                                    ctx.format_shortcut(&self.pause_shortcut)
                                 )),
                             )
                             .clicked()
                         {
-                            self.start_clicker(ctx);
+                            self.toggle_pause();
                         }
                     });
 
@@ -337,156 +2563,266 @@ impl eframe::App for ClickApp {
                     });
                 });
 
+                if let Some(recorder) = &self.macro_recorder {
+                    ui.add_space(6.0);
+                    ui.vertical_centered(|ui| {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("● Recording · {} event(s)", recorder.event_count()),
+                        )
+                    });
+                } else if self.clicker.is_running
+                    && let Some(status) = self.status_text()
+                {
+                    ui.add_space(6.0);
+                    ui.vertical_centered(|ui| ui.label(status));
+                    ctx.request_repaint_after(Duration::from_millis(250));
+                } else if !self.clicker.is_running
+                    && let Some(error) = self
+                        .clicker
+                        .last_error
+                        .as_ref()
+                        .or(self.screen_error.as_ref())
+                {
+                    ui.add_space(6.0);
+                    ui.vertical_centered(|ui| ui.colored_label(ui.visuals().error_fg_color, error));
+                }
+
+                if let Some(error) = &self.settings_error {
+                    ui.add_space(6.0);
+                    ui.vertical_centered(|ui| {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("Saved settings were reset: {error}"),
+                        )
+                    });
+                }
+                if let Some(error) = &self.config_error {
+                    ui.add_space(6.0);
+                    ui.vertical_centered(|ui| {
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("Config file: {error}"),
+                        )
+                    });
+                }
+
                 egui::warn_if_debug_build(ui);
             });
 
-        // Main configuration panel
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.add_enabled_ui(!self.clicker.is_running, |ui| {
-                // Interval section
-                ui.heading("Interval");
-                ui.add_space(6.0);
-
-                egui::Grid::new("interval_grid")
-                    .num_columns(2)
-                    .spacing([10.0, 4.0])
-                    .show(ui, |ui| {
-                        ui.radio_value(&mut self.interval_mode, IntervalMode::Time, "Time:");
-                        ui.add_enabled_ui(self.interval_mode == IntervalMode::Time, |ui| {
-                            ui.horizontal(|ui| {
-                                ui.label("H:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.time_interval.hours)
-                                        .speed(0.1)
-                                        .range(0..=23),
-                                );
-                                ui.label("M:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.time_interval.minutes)
-                                        .speed(0.1)
-                                        .range(0..=59),
-                                );
-                                ui.label("S:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.time_interval.seconds)
-                                        .speed(0.1)
-                                        .range(0..=59),
-                                );
-                                ui.label("MS:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.time_interval.milliseconds)
-                                        .speed(1.0)
-                                        .range(0..=999),
-                                );
-                            });
-                        });
-                        ui.end_row();
+        egui::TopBottomPanel::top("tab_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::Settings, "Settings");
+                ui.selectable_value(&mut self.tab, Tab::Statistics, "Statistics");
+                ui.selectable_value(&mut self.tab, Tab::Macro, "Macro");
+                ui.selectable_value(&mut self.tab, Tab::Sequence, "Sequence");
+                ui.selectable_value(&mut self.tab, Tab::Script, "Script");
+                ui.selectable_value(&mut self.tab, Tab::Rules, "Rules");
+            });
+        });
 
-                        ui.radio_value(&mut self.interval_mode, IntervalMode::Cps, "Target CPS:");
-                        ui.add_enabled_ui(self.interval_mode == IntervalMode::Cps, |ui| {
-                            ui.add(
-                                egui::DragValue::new(&mut self.cps)
-                                    .speed(0.1)
-                                    .range(1..=1000),
-                            );
-                        });
-                        ui.end_row();
-                    });
+        // Main panel
+        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
+            Tab::Settings => {
+                egui::ScrollArea::vertical().show(ui, |ui| self.settings_ui(ui));
+            }
+            Tab::Statistics => self.statistics_ui(ui),
+            Tab::Macro => self.macro_ui(ui),
+            Tab::Sequence => self.sequence_ui(ui),
+            Tab::Script => self.script_ui(ui),
+            Tab::Rules => self.rules_ui(ui),
+        });
 
-                ui.add(egui::Separator::default().spacing(18.0));
+        self.update_window_title(ctx);
+        self.push_live_settings();
+    }
+}
 
-                // Behavior section
-                ui.heading("Behavior");
-                ui.add_space(6.0);
+/// Size in pixels of the monitor the window is on, which absolute positions in
+/// evemu recordings are scaled to
+fn screen_size(ctx: &egui::Context) -> Option<ScreenSize> {
+    let size = ctx.input(|i| i.viewport().monitor_size)? * ctx.pixels_per_point();
+    Some(ScreenSize {
+        width: size.x.round() as u32,
+        height: size.y.round() as u32,
+    })
+}
 
-                egui::Grid::new("behavior_grid")
-                    .num_columns(2)
-                    .spacing([10.0, 4.0])
-                    .show(ui, |ui| {
-                        ui.label("Mouse button:");
-                        egui::ComboBox::from_id_salt("mouse_button")
-                            .selected_text(self.mouse_button.to_string())
-                            .show_ui(ui, |ui| {
-                                for variant in MouseButton::all() {
-                                    ui.selectable_value(
-                                        &mut self.mouse_button,
-                                        variant,
-                                        variant.to_string(),
-                                    );
-                                }
-                            });
-                        ui.end_row();
+/// Whole seconds left before a color pick for `target` happens, if one is waiting
+fn pick_countdown(pick: Option<(Instant, PickTarget)>, target: PickTarget) -> Option<u64> {
+    let (at, _) = pick.filter(|(_, picking)| *picking == target)?;
+    Some(
+        at.saturating_duration_since(Instant::now())
+            .as_secs_f64()
+            .ceil() as u64,
+    )
+}
 
-                        ui.label("Click type:");
-                        egui::ComboBox::from_id_salt("click_type")
-                            .selected_text(self.click_type.to_string())
-                            .show_ui(ui, |ui| {
-                                for variant in ClickAction::all() {
-                                    ui.selectable_value(
-                                        &mut self.click_type,
-                                        variant,
-                                        variant.to_string(),
-                                    );
-                                }
-                            });
-                        ui.end_row();
+/// Shows the editor for one step, returning whether a color pick was asked for
+fn step_editor(
+    ui: &mut egui::Ui,
+    index: usize,
+    step: &mut Step,
+    labels: &[String],
+    picking: Option<u64>,
+) -> bool {
+    let mut pick = false;
+    match step {
+        Step::Click {
+            button,
+            action,
+            use_location,
+            location,
+        } => {
+            egui::ComboBox::from_id_salt(("step_button", index))
+                .width(60.0)
+                .selected_text(button.to_string())
+                .show_ui(ui, |ui| {
+                    for variant in MouseButton::all() {
+                        ui.selectable_value(button, variant, variant.to_string());
+                    }
+                });
+            egui::ComboBox::from_id_salt(("step_action", index))
+                .width(60.0)
+                .selected_text(action.to_string())
+                .show_ui(ui, |ui| {
+                    for variant in ClickAction::all() {
+                        ui.selectable_value(action, variant, variant.to_string());
+                    }
+                });
+            ui.checkbox(use_location, "at");
+            ui.add_enabled_ui(*use_location, |ui| {
+                widgets::coordinates_editor(ui, location);
+            });
+        }
+        Step::Move { location } => widgets::coordinates_editor(ui, location),
+        Step::WaitColor { condition } => {
+            pick = widgets::pixel_condition_editor(ui, condition, picking);
+        }
+        Step::Wait {
+            millis,
+            use_random,
+            max_millis,
+        } => {
+            ui.add(egui::DragValue::new(millis).speed(1.0).suffix(" ms"));
+            ui.checkbox(use_random, "to");
+            ui.add_enabled(
+                *use_random,
+                egui::DragValue::new(max_millis)
+                    .speed(1.0)
+                    .range(*millis..=u32::MAX)
+                    .suffix(" ms"),
+            );
+        }
+        Step::Key { code } => {
+            egui::ComboBox::from_id_salt(("step_key", index))
+                .width(80.0)
+                .selected_text(keys::name(*code))
+                .show_ui(ui, |ui| {
+                    for (key, name) in keys::KEYS {
+                        ui.selectable_value(code, *key, *name);
+                    }
+                });
+        }
+        Step::Repeat { times } => {
+            ui.add(
+                egui::DragValue::new(times)
+                    .speed(0.1)
+                    .range(0..=u32::MAX)
+                    .suffix("×"),
+            );
+        }
+        Step::EndRepeat => {}
+        Step::Label { name } => {
+            ui.add(egui::TextEdit::singleline(name).desired_width(100.0));
+        }
+        Step::Jump { label } => {
+            egui::ComboBox::from_id_salt(("step_jump", index))
+                .width(100.0)
+                .selected_text(label.as_str())
+                .show_ui(ui, |ui| {
+                    for name in labels {
+                        ui.selectable_value(label, name.clone(), name);
+                    }
+                });
+        }
+    }
+    pick
+}
 
-                        ui.checkbox(&mut self.use_num_clicks, "Repeat only:");
-                        ui.horizontal(|ui| {
-                            ui.add_enabled_ui(self.use_num_clicks, |ui| {
-                                ui.add(
-                                    egui::DragValue::new(&mut self.num_clicks)
-                                        .speed(1.0)
-                                        .range(1..=u32::MAX),
-                                );
-                            });
-                            ui.label("click(s)");
-                        });
-                        ui.end_row();
-                    });
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                ui.add(egui::Separator::default().spacing(18.0));
+    fn app_with_rules() -> ClickApp {
+        let mut app = ClickApp::default();
+        app.duplicate_profile();
+        app.profiles[1].name = "Game".to_owned();
+        app.app_rules = vec![
+            AppRule {
+                pattern: "game".to_owned(),
+                profile: "Game".to_owned(),
+                ..AppRule::default()
+            },
+            AppRule {
+                pattern: "editor".to_owned(),
+                profile: profiles::DEFAULT_NAME.to_owned(),
+                ..AppRule::default()
+            },
+        ];
+        app
+    }
 
-                // Extra section
-                ui.heading("Extra");
-                ui.add_space(6.0);
+    #[test]
+    fn deleting_a_profile_removes_its_rules() {
+        let mut app = app_with_rules();
+        app.active_profile = 1;
+        app.delete_profile();
 
-                egui::Grid::new("extra_grid")
-                    .num_columns(2)
-                    .spacing([10.0, 4.0])
-                    .show(ui, |ui| {
-                        ui.checkbox(&mut self.use_jitter, "Random delay:");
-                        ui.add_enabled_ui(self.use_jitter, |ui| {
-                            ui.add(
-                                egui::DragValue::new(&mut self.jitter)
-                                    .speed(0.1)
-                                    .range(0..=1000)
-                                    .prefix("± ")
-                                    .suffix(" ms"),
-                            );
-                        });
-                        ui.end_row();
+        assert_eq!(app.profiles.len(), 1);
+        assert_eq!(app.app_rules.len(), 1);
+        assert_eq!(app.app_rules[0].pattern, "editor");
+    }
 
-                        ui.checkbox(&mut self.use_location, "Location:");
-                        ui.add_enabled_ui(self.use_location, |ui| {
-                            ui.horizontal(|ui| {
-                                ui.label("X:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.location.x)
-                                        .speed(1.0)
-                                        .range(0..=i32::MAX),
-                                );
-                                ui.label("Y:");
-                                ui.add(
-                                    egui::DragValue::new(&mut self.location.y)
-                                        .speed(1.0)
-                                        .range(0..=i32::MAX),
-                                );
-                            });
-                        });
-                        ui.end_row();
-                    });
-            });
+    #[test]
+    fn rules_for_missing_profiles_are_not_in_target() {
+        let mut app = app_with_rules();
+        app.focused = Some(FocusedWindow {
+            class: "Game".to_owned(),
+            ..FocusedWindow::default()
         });
+        assert!(app.focused_rule().is_some());
+
+        app.app_rules[0].profile = "Gone".to_owned();
+        assert!(app.focused_rule().is_none());
+    }
+
+    #[test]
+    fn hand_edited_values_are_clamped_and_reported() {
+        let mut app = ClickApp::from_saved(serde_json::json!({
+            "version": migrate::VERSION,
+            "cps": 5000.0,
+            "ramp": { "start_cps": 0.0 },
+            "image_target": { "confidence": 0.1 },
+            "profiles": [{ "name": "Fast", "settings": { "jitter": 4000 } }],
+        }))
+        .unwrap();
+
+        let clamped = app.clamp_to_ranges();
+        assert_eq!(app.cps, MAX_CPS);
+        assert_eq!(app.ramp.start_cps, MIN_CPS);
+        assert_eq!(app.image_target.confidence, 0.5);
+        assert_eq!(app.profiles[0].settings.jitter, 1000);
+        assert_eq!(
+            clamped,
+            [
+                "cps 5000 to 1000",
+                "ramp.start_cps 0 to 0.01",
+                "profiles[0].settings.jitter 4000 to 1000",
+                "image_target.confidence 0.1 to 0.5",
+            ]
+        );
+        assert!(ClickerConfig::from_app(&app, Duration::ZERO).is_ok());
+        assert!(app.clamp_to_ranges().is_empty());
     }
 }
@@ -0,0 +1,127 @@
+//! The settings as a TOML file that can be edited by hand or kept with
+//! dotfiles, reloaded whenever it changes on disk
+
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+};
+
+use anyhow::{Context, Result, anyhow};
+use eframe::egui;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+
+const DIRECTORY: &str = "click";
+const FILE_NAME: &str = "config.toml";
+
+/// Where the file lives: `$XDG_CONFIG_HOME/click/config.toml` on Linux, and
+/// the usual config folder elsewhere
+pub fn path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join(DIRECTORY).join(FILE_NAME))
+}
+
+/// Points at the line and column of a TOML error, like the other file formats
+fn toml_error(text: &str, e: toml::de::Error) -> anyhow::Error {
+    let Some(span) = e.span() else {
+        return anyhow!("{}", e.message());
+    };
+    let before = &text[..span.start.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+    anyhow!("Line {line}, column {column}: {}", e.message())
+}
+
+/// The config file and a watcher that notices when it changes
+pub struct ConfigFile {
+    path: PathBuf,
+    /// Settings as last read or written, so unchanged settings aren't written
+    /// back over the user's formatting and our own writes aren't reloaded
+    saved: String,
+    changes: Receiver<()>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigFile {
+    /// Starts watching `path`, repainting `ctx` when it changes so the new
+    /// settings are picked up straight away
+    pub fn watch(path: PathBuf, ctx: egui::Context) -> Result<Self> {
+        // Editors often save by replacing the file, so watch its folder instead
+        let directory = path.parent().context("The config path has no folder")?;
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Couldn't create {}", directory.display()))?;
+
+        let (sender, changes) = mpsc::channel();
+        let name = path.file_name().map(ToOwned::to_owned);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let ours = event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == name.as_deref());
+                if ours && !matches!(event.kind, EventKind::Access(_)) {
+                    let _ = sender.send(());
+                    ctx.request_repaint();
+                }
+            })?;
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Couldn't watch {}", directory.display()))?;
+
+        Ok(Self {
+            path,
+            saved: String::new(),
+            changes,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Whether the file has changed since this was last asked
+    pub fn changed(&self) -> bool {
+        self.changes.try_iter().count() > 0
+    }
+
+    /// Reads the settings with `parse`, which gets them as a tree so older ones
+    /// can be upgraded first. Returns `None` if the file doesn't exist or holds
+    /// the settings last read or written.
+    pub fn read<T: Serialize>(
+        &mut self,
+        parse: impl FnOnce(serde_json::Value) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Couldn't read {}", self.path.display()));
+            }
+        };
+
+        let tree = toml::from_str(&text).map_err(|e| toml_error(&text, e))?;
+        let settings = parse(tree)?;
+        let saved = toml::to_string(&settings)?;
+        if saved == self.saved {
+            return Ok(None);
+        }
+        self.saved = saved;
+        Ok(Some(settings))
+    }
+
+    /// Writes the settings if they differ from those last read or written
+    pub fn write<T: Serialize>(&mut self, settings: &T) -> Result<()> {
+        let text = toml::to_string(settings)?;
+        if text == self.saved {
+            return Ok(());
+        }
+        fs::write(&self.path, &text)
+            .with_context(|| format!("Couldn't write {}", self.path.display()))?;
+        self.saved = text;
+        Ok(())
+    }
+}
@@ -0,0 +1,1515 @@
+//! The clicker itself, which the window and `click run` are both built on and
+//! which other programs can embed.
+//!
+//! A run is described by a [`ClickerConfig`] and started as a [`Clicker`] on a
+//! tokio runtime. The handle pauses, resumes and stops it, changes its
+//! [`LiveSettings`] on the fly and reports its [`Status`] and [`Event`]s:
+//!
+//! ```no_run
+//! use std::sync::{Arc, Mutex};
+//!
+//! use click::{
+//!     Clicker, ClickerConfig, InputHandler,
+//!     input::{Coordinates, MouseButton},
+//! };
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let runtime = tokio::runtime::Runtime::new()?;
+//! let config = ClickerConfig::builder()
+//!     .cps(25.0)
+//!     .button(MouseButton::Left)
+//!     .click_limit(500)
+//!     .location(Coordinates { x: 100, y: 200 })
+//!     .build()?;
+//! let input = Arc::new(Mutex::new(InputHandler::new()?));
+//!
+//! let mut clicker = Clicker::start(config, input, runtime.handle(), || {});
+//! let ending = runtime.block_on(clicker.finished());
+//! println!("{ending:?}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Clicks go through an [`InputHandler`], which sends them with the platform's
+//! input backend unless it was made [with another one](InputHandler::with_backend).
+
+use anyhow::bail;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, watch},
+};
+
+use crate::{
+    InputHandler,
+    input::{CaptureKind, ClickAction, Coordinates, DOUBLE_CLICK_GAP, MOVE_SETTLE, MouseButton},
+    interval::{BurstPattern, Jitter, Ramp},
+    learned::LearnedProfile,
+    macros::{MacroPlayer, Playback},
+    screen::{PixelCondition, Screen},
+    script::{self, Script, ScriptControl, ScriptEnd, ScriptOptions},
+    sequence::{Action, Program, SequenceRunner},
+    stats::ClickSample,
+    template::{ImageTarget, Located, MissAction, TargetFinder},
+};
+
+/// Slowest click rate offered, one click every 100 seconds
+pub const MIN_CPS: f64 = 0.01;
+/// Fastest click rate offered
+pub const MAX_CPS: f64 = 1000.0;
+
+/// How often a sequence checks the screen while waiting for a color
+const COLOR_POLL: Duration = Duration::from_millis(50);
+
+/// Capacity of the event channel; events are dropped rather than blocking clicks
+const EVENT_BUFFER: usize = 4096;
+
+enum Command {
+    Stop,
+    Pause,
+    Resume,
+}
+
+/// How a run ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ending {
+    /// It ran out of clicks, time, macro, sequence or script
+    Completed,
+    /// It was stopped, or its [`Clicker`] was dropped
+    Stopped,
+    /// Something went wrong, described for showing to the user
+    Failed(String),
+}
+
+/// Where a run is up to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    /// Waiting out the start delay
+    Waiting,
+    Running,
+    Paused,
+    Ended(Ending),
+}
+
+/// Something that happened during a run
+#[derive(Clone, Debug)]
+pub enum Event {
+    Clicked(ClickSample),
+    StatusChanged(Status),
+}
+
+/// Everything a run does, built with [`ClickerConfig::builder`]
+#[derive(Clone)]
+pub struct ClickerConfig {
+    base_interval: Duration,
+    ramp: Option<Ramp>,
+    mouse_button: MouseButton,
+    click_action: ClickAction,
+    use_jitter: bool,
+    jitter: u16,
+    seed: u64,
+    learned_profile: Option<LearnedProfile>,
+    use_location: bool,
+    location: Coordinates,
+    use_num_clicks: bool,
+    num_clicks: u32,
+    burst: Option<BurstPattern>,
+    use_num_bursts: bool,
+    num_bursts: u32,
+    /// Wait before the first click, for a scheduled start time
+    start_delay: Duration,
+    /// How long clicking may run before it stops on its own
+    run_limit: Option<Duration>,
+    /// Replays the recorded macro in place of single clicks
+    playback: Option<Playback>,
+    /// Runs the sequence in place of single clicks
+    program: Option<Program>,
+    /// Runs the script in place of single clicks
+    script: Option<Script>,
+    script_runs: Option<u32>,
+    /// Skips clicks while this isn't met
+    pixel_condition: Option<PixelCondition>,
+    /// Clicks where this is found in place of `location`
+    image_target: Option<ImageTarget>,
+}
+
+impl Default for ClickerConfig {
+    /// Left clicks at 20 CPS wherever the pointer is, until stopped
+    fn default() -> Self {
+        Self {
+            base_interval: Duration::from_millis(50),
+            ramp: None,
+            mouse_button: MouseButton::Left,
+            click_action: ClickAction::Single,
+            use_jitter: false,
+            jitter: 0,
+            seed: rand::random(),
+            learned_profile: None,
+            use_location: false,
+            location: Coordinates::default(),
+            use_num_clicks: false,
+            num_clicks: 0,
+            burst: None,
+            use_num_bursts: false,
+            num_bursts: 0,
+            start_delay: Duration::ZERO,
+            run_limit: None,
+            playback: None,
+            program: None,
+            script: None,
+            script_runs: None,
+            pixel_condition: None,
+            image_target: None,
+        }
+    }
+}
+
+/// Builds a [`ClickerConfig`], starting from [its defaults](ClickerConfig::default)
+#[derive(Clone, Default)]
+pub struct ClickerBuilder {
+    config: ClickerConfig,
+    /// Checked and turned into the interval by [`build`](Self::build)
+    cps: Option<f64>,
+}
+
+impl ClickerBuilder {
+    /// Time between clicks, which must be longer than zero
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.config.base_interval = interval;
+        self.cps = None;
+        self
+    }
+
+    /// Clicks per second between [`MIN_CPS`] and [`MAX_CPS`], in place of
+    /// [`interval`](Self::interval)
+    pub fn cps(mut self, cps: f64) -> Self {
+        self.cps = Some(cps);
+        self
+    }
+
+    pub fn button(mut self, button: MouseButton) -> Self {
+        self.config.mouse_button = button;
+        self
+    }
+
+    pub fn action(mut self, action: ClickAction) -> Self {
+        self.config.click_action = action;
+        self
+    }
+
+    /// Varies the timing by about this many milliseconds
+    pub fn jitter(mut self, jitter: u16) -> Self {
+        self.config.use_jitter = true;
+        self.config.jitter = jitter;
+        self
+    }
+
+    /// Shapes the jitter like the user's own clicking. A profile that isn't
+    /// usable is ignored and the plain jitter is used instead
+    pub fn learned_profile(mut self, profile: LearnedProfile) -> Self {
+        self.config.learned_profile = Some(profile).filter(LearnedProfile::is_usable);
+        self
+    }
+
+    /// Seeds the jitter and other randomness, to repeat an earlier run
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    /// Clicks at `location` instead of wherever the pointer is
+    pub fn location(mut self, location: Coordinates) -> Self {
+        self.config.use_location = true;
+        self.config.location = location;
+        self
+    }
+
+    /// Completes after this many clicks
+    pub fn click_limit(mut self, clicks: u32) -> Self {
+        self.config.use_num_clicks = true;
+        self.config.num_clicks = clicks;
+        self
+    }
+
+    /// Clicks in bursts with rests between them, in place of the interval
+    pub fn burst(mut self, burst: BurstPattern) -> Self {
+        self.config.burst = Some(burst);
+        self
+    }
+
+    /// Completes after this many bursts
+    pub fn burst_limit(mut self, bursts: u32) -> Self {
+        self.config.use_num_bursts = true;
+        self.config.num_bursts = bursts;
+        self
+    }
+
+    /// Speeds up or slows down the clicking over the run
+    pub fn ramp(mut self, ramp: Ramp) -> Self {
+        self.config.ramp = Some(ramp);
+        self
+    }
+
+    /// Waits this long before the first click
+    pub fn start_delay(mut self, delay: Duration) -> Self {
+        self.config.start_delay = delay;
+        self
+    }
+
+    /// Completes after clicking for this long, not counting pauses
+    pub fn run_limit(mut self, limit: Duration) -> Self {
+        self.config.run_limit = Some(limit);
+        self
+    }
+
+    /// Replays a macro in place of single clicks
+    pub fn playback(mut self, playback: Playback) -> Self {
+        self.config.playback = Some(playback);
+        self
+    }
+
+    /// Runs a compiled sequence in place of single clicks
+    pub fn program(mut self, program: Program) -> Self {
+        self.config.program = Some(program);
+        self
+    }
+
+    /// Runs a script in place of single clicks
+    pub fn script(mut self, script: Script) -> Self {
+        self.config.script = Some(script);
+        self
+    }
+
+    /// Completes after the script has run this many times
+    pub fn script_runs(mut self, runs: u32) -> Self {
+        self.config.script_runs = Some(runs);
+        self
+    }
+
+    /// Skips clicks while `condition` isn't met
+    pub fn pixel_condition(mut self, condition: PixelCondition) -> Self {
+        self.config.pixel_condition = Some(condition);
+        self
+    }
+
+    /// Clicks wherever `target` is found on screen
+    pub fn image_target(mut self, target: ImageTarget) -> Self {
+        self.config.image_target = Some(target);
+        self
+    }
+
+    /// Checks the settings, failing on a click rate out of range or an empty
+    /// interval
+    pub fn build(mut self) -> anyhow::Result<ClickerConfig> {
+        let in_range = |cps: f64| (MIN_CPS..=MAX_CPS).contains(&cps);
+        if let Some(cps) = self.cps {
+            if !in_range(cps) {
+                bail!("The click rate must be between {MIN_CPS} and {MAX_CPS} CPS, not {cps}");
+            }
+            self.config.base_interval = Duration::from_secs_f64(1.0 / cps);
+        }
+        check_interval(self.config.base_interval)?;
+        if let Some(ramp) = &self.config.ramp
+            && !in_range(ramp.start_cps)
+        {
+            bail!(
+                "The ramp must start between {MIN_CPS} and {MAX_CPS} CPS, not {}",
+                ramp.start_cps
+            );
+        }
+        Ok(self.config)
+    }
+}
+
+fn check_interval(interval: Duration) -> anyhow::Result<()> {
+    if interval.is_zero() {
+        bail!("The time between clicks must be longer than zero");
+    }
+    Ok(())
+}
+
+/// The part of a [`ClickerConfig`] that can be changed while the clicker runs
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiveSettings {
+    pub base_interval: Duration,
+    pub mouse_button: MouseButton,
+    pub click_action: ClickAction,
+    pub use_jitter: bool,
+    pub jitter: u16,
+    pub use_location: bool,
+    pub location: Coordinates,
+}
+
+impl ClickerConfig {
+    pub fn builder() -> ClickerBuilder {
+        ClickerBuilder::default()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn start_delay(&self) -> Duration {
+        self.start_delay
+    }
+
+    pub fn run_limit(&self) -> Option<Duration> {
+        self.run_limit
+    }
+
+    /// The settings a run starts with that can be changed while it goes
+    pub fn live(&self) -> LiveSettings {
+        LiveSettings {
+            base_interval: self.base_interval,
+            mouse_button: self.mouse_button,
+            click_action: self.click_action,
+            use_jitter: self.use_jitter,
+            jitter: self.jitter,
+            use_location: self.use_location,
+            location: self.location,
+        }
+    }
+
+    /// Returns the delays before the first `count` clicks, jittered with `seed`,
+    /// for previewing the timing
+    pub fn preview_delays(&self, seed: u64, count: usize) -> Vec<Duration> {
+        let mut jitter_gen = Jitter::from_seed(seed).with_profile(self.learned_profile.clone());
+        let mut elapsed = Duration::ZERO;
+
+        (0..count as u32)
+            .map(|click_index| {
+                let base = self.delay_before(click_index, elapsed);
+                let delay = if self.uses_jitter() {
+                    jitter_gen.next(base, self.jitter)
+                } else {
+                    base
+                };
+                elapsed += delay;
+                delay
+            })
+            .collect()
+    }
+
+    fn apply(&mut self, live: &LiveSettings) {
+        self.base_interval = live.base_interval;
+        self.mouse_button = live.mouse_button;
+        self.click_action = live.click_action;
+        self.use_jitter = live.use_jitter;
+        self.jitter = live.jitter;
+        self.use_location = live.use_location;
+        self.location = live.location;
+    }
+
+    fn uses_jitter(&self) -> bool {
+        self.use_jitter && (self.jitter > 0 || self.learned_profile.is_some())
+    }
+
+    /// Returns the base delay before click number `click_index`, with the ramp
+    /// applied to everything but burst rests
+    fn delay_before(&self, click_index: u32, elapsed: Duration) -> Duration {
+        let interval = match &self.burst {
+            Some(burst) if burst.rests_before(click_index) => return burst.rest.to_duration(),
+            Some(burst) => burst.interval.to_duration(),
+            None => self.base_interval,
+        };
+
+        match &self.ramp {
+            Some(ramp) => {
+                let remaining = self
+                    .click_limit()
+                    .map(|limit| limit.saturating_sub(click_index));
+                ramp.apply(interval, elapsed, remaining)
+            }
+            None => interval,
+        }
+    }
+
+    /// Returns the average interval the run aims for, rests included, unless
+    /// the timing comes from a macro or sequence
+    pub fn target_interval(&self) -> Option<Duration> {
+        if self.playback.is_some() || self.program.is_some() || self.script.is_some() {
+            return None;
+        }
+
+        Some(match &self.burst {
+            Some(burst) => {
+                let clicks = burst.clicks.max(1);
+                (burst.interval.to_duration() * (clicks - 1) + burst.rest.to_duration()) / clicks
+            }
+            None => self.base_interval,
+        })
+    }
+
+    /// Returns the number of clicks after which the run completes, if any
+    pub fn click_limit(&self) -> Option<u32> {
+        let click_limit = self.use_num_clicks.then_some(self.num_clicks);
+        let burst_limit = self
+            .burst
+            .filter(|_| self.use_num_bursts)
+            .map(|burst| burst.clicks_in(self.num_bursts));
+
+        match (click_limit, burst_limit) {
+            (Some(clicks), Some(bursts)) => Some(clicks.min(bursts)),
+            (limit, None) | (None, limit) => limit,
+        }
+    }
+}
+
+/// A running clicker. Dropping it stops the run.
+pub struct Clicker {
+    commands: mpsc::Sender<Command>,
+    live: watch::Sender<LiveSettings>,
+    status: watch::Receiver<Status>,
+    events: mpsc::Receiver<Event>,
+}
+
+impl Clicker {
+    /// Starts a run on `runtime`, sending input through `input`. `notify` is
+    /// called whenever the status changes, for waking up a UI.
+    pub fn start(
+        config: ClickerConfig,
+        input: Arc<Mutex<InputHandler>>,
+        runtime: &Handle,
+        notify: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        let (commands, command_rx) = mpsc::channel(4);
+        let (live, live_rx) = watch::channel(config.live());
+        let (status_tx, status) = watch::channel(Status::Waiting);
+        let (event_tx, events) = mpsc::channel(EVENT_BUFFER);
+
+        let mut task = Task {
+            commands: command_rx,
+            status: status_tx,
+            events: event_tx,
+            notify: Box::new(notify),
+        };
+        runtime.spawn(async move {
+            let ending = drive(config, input, &mut task, live_rx).await;
+            task.set_status(Status::Ended(ending));
+        });
+
+        Self {
+            commands,
+            live,
+            status,
+            events,
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status.borrow().clone()
+    }
+
+    /// Waits for the run to end
+    pub async fn finished(&mut self) -> Ending {
+        let status = self
+            .status
+            .wait_for(|status| matches!(status, Status::Ended(_)))
+            .await;
+        match status.as_deref() {
+            Ok(Status::Ended(ending)) => ending.clone(),
+            _ => Ending::Failed("The clicker stopped unexpectedly".to_owned()),
+        }
+    }
+
+    /// Returns the next event if there is one. Events that aren't read in time
+    /// are dropped, so clicks never wait on the reader.
+    pub fn try_event(&mut self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    /// Waits for the next event, returning `None` once the run has ended and
+    /// every event has been read
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    /// Pauses clicking, returning whether the clicker was told to. Pausing
+    /// during the start delay holds the countdown.
+    pub fn pause(&self) -> bool {
+        self.commands.try_send(Command::Pause).is_ok()
+    }
+
+    /// Resumes after [`pause`](Self::pause), returning whether the clicker was told to
+    pub fn resume(&self) -> bool {
+        self.commands.try_send(Command::Resume).is_ok()
+    }
+
+    /// Stops the run, releasing anything a macro holds. Wait for
+    /// [`finished`](Self::finished) to know it has.
+    pub fn stop(&self) {
+        let _ = self.commands.try_send(Command::Stop);
+    }
+
+    /// Applies `live` from the next click on, returning whether it changed
+    /// anything. Fails without changing the run on an interval the builder
+    /// would refuse.
+    pub fn update(&self, live: LiveSettings) -> anyhow::Result<bool> {
+        check_interval(live.base_interval)?;
+        Ok(self.live.send_if_modified(|current| {
+            if *current == live {
+                return false;
+            }
+            *current = live;
+            true
+        }))
+    }
+}
+
+/// The run's side of a [`Clicker`]
+struct Task {
+    commands: mpsc::Receiver<Command>,
+    status: watch::Sender<Status>,
+    events: mpsc::Sender<Event>,
+    notify: Box<dyn Fn() + Send + Sync>,
+}
+
+impl Task {
+    /// Sends the event first, so it can be read by anyone who sees the new status
+    fn set_status(&self, status: Status) {
+        let _ = self.events.try_send(Event::StatusChanged(status.clone()));
+        self.status.send_replace(status);
+        (self.notify)();
+    }
+
+    fn clicked(&self, sample: ClickSample) {
+        let _ = self.events.try_send(Event::Clicked(sample));
+    }
+
+    /// Waits while paused, returning whether to resume (`false` means stop).
+    /// Resuming goes back to the status from before the pause.
+    async fn wait_for_resume(&mut self) -> bool {
+        let before = self.status.borrow().clone();
+        self.set_status(Status::Paused);
+        loop {
+            match self.commands.recv().await {
+                Some(Command::Resume) => {
+                    self.set_status(before);
+                    return true;
+                }
+                Some(Command::Pause) => {}
+                Some(Command::Stop) | None => return false,
+            }
+        }
+    }
+}
+
+/// Completes at `deadline`, or never if there is none
+async fn wait_for_deadline(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Works out when the click after `last_tick` is due, or `None` when the
+/// plain interval keeps time
+fn next_due(
+    config: &ClickerConfig,
+    jitter_gen: &mut Jitter,
+    last_tick: tokio::time::Instant,
+    base: Duration,
+) -> Option<tokio::time::Instant> {
+    if config.uses_jitter() {
+        Some(last_tick + jitter_gen.next(base, config.jitter))
+    } else if config.burst.is_some() || config.ramp.is_some() {
+        Some(last_tick + base)
+    } else {
+        None
+    }
+}
+
+/// Waits until `due`, or for the next tick of `interval` without one, and
+/// returns when the click was scheduled
+async fn wait_for_tick(
+    interval: &mut tokio::time::Interval,
+    due: Option<tokio::time::Instant>,
+) -> tokio::time::Instant {
+    match due {
+        Some(due) => {
+            tokio::time::sleep_until(due).await;
+            due
+        }
+        None => interval.tick().await,
+    }
+}
+
+/// Replays `playback` until it finishes, hits a limit or is stopped, returning
+/// how it ended. Anything still held is released.
+async fn play_macro(
+    playback: Playback,
+    config: &ClickerConfig,
+    input_handler: &Arc<Mutex<InputHandler>>,
+    jitter_gen: &mut Jitter,
+    task: &mut Task,
+    mut deadline: Option<tokio::time::Instant>,
+) -> Ending {
+    let mut player = MacroPlayer::new(playback);
+    let click_limit = config.click_limit();
+    let mut click_count = 0u32;
+    let mut next_event = tokio::time::Instant::now();
+    // An event waiting for its time, kept across pauses
+    let mut pending = None;
+
+    let status = loop {
+        let event = match pending {
+            Some(event) => event,
+            None => {
+                let Some(mut event) = player.next() else {
+                    break Ending::Completed;
+                };
+                // Only gaps before presses are varied, so recorded motion stays smooth
+                let is_press = matches!(
+                    event.kind,
+                    CaptureKind::Button { pressed: true, .. }
+                        | CaptureKind::Key { pressed: true, .. }
+                );
+                if is_press && config.uses_jitter() {
+                    event.delay = jitter_gen.next(event.delay, config.jitter);
+                }
+                next_event += event.delay;
+                pending = Some(event);
+                event
+            }
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_event) => {
+                pending = None;
+                let is_click = matches!(event.kind, CaptureKind::Button { pressed: true, .. });
+                if is_click && click_limit.is_some_and(|limit| click_count >= limit) {
+                    break Ending::Completed;
+                }
+
+                if let Err(e) = send_input(input_handler, event.kind) {
+                    break Ending::Failed(e);
+                }
+                player.sent(event.kind);
+
+                if is_click {
+                    click_count += 1;
+                    task.clicked(ClickSample {
+                        count: click_count as u64,
+                        scheduled: next_event.into_std(),
+                        clicked: Instant::now(),
+                    });
+                }
+            }
+            _ = wait_for_deadline(deadline) => break Ending::Completed,
+            command = task.commands.recv() => match command {
+                Some(Command::Pause) => {
+                    // Let go of everything while paused, and pick it back up on resume
+                    for release in player.releases() {
+                        let _ = send_input(input_handler, release);
+                    }
+
+                    let paused_at = tokio::time::Instant::now();
+                    if !task.wait_for_resume().await {
+                        break Ending::Stopped;
+                    }
+
+                    let paused_for = paused_at.elapsed();
+                    next_event += paused_for;
+                    if let Some(deadline) = &mut deadline {
+                        *deadline += paused_for;
+                    }
+                    for held in player.held() {
+                        let _ = send_input(input_handler, *held);
+                    }
+                }
+                Some(Command::Resume) => {}
+                Some(Command::Stop) | None => break Ending::Stopped,
+            },
+        }
+    };
+
+    for release in player.releases() {
+        let _ = send_input(input_handler, release);
+    }
+    status
+}
+
+/// Runs a script on a blocking thread, passing on pause and stop commands and
+/// the deadline, and returns how it ended
+async fn run_script(
+    script: Script,
+    options: ScriptOptions,
+    input_handler: &Arc<Mutex<InputHandler>>,
+    task: &mut Task,
+    mut deadline: Option<tokio::time::Instant>,
+) -> Ending {
+    let control = Arc::new(ScriptControl::new());
+    let mut handle = tokio::task::spawn_blocking({
+        let input_handler = Arc::clone(input_handler);
+        let control = Arc::clone(&control);
+        let events = task.events.clone();
+        move || script::run(script, options, input_handler, control, events)
+    });
+
+    loop {
+        tokio::select! {
+            result = &mut handle => {
+                return match result {
+                    Ok(Ok(ScriptEnd::Finished)) => Ending::Completed,
+                    Ok(Ok(ScriptEnd::Stopped)) => Ending::Stopped,
+                    Ok(Err(e)) => Ending::Failed(e.to_string()),
+                    Err(e) => Ending::Failed(format!("Script thread failed: {e}")),
+                };
+            }
+            _ = wait_for_deadline(deadline) => {
+                control.stop();
+                let _ = (&mut handle).await;
+                return Ending::Completed;
+            }
+            command = task.commands.recv() => match command {
+                Some(Command::Pause) => {
+                    control.pause();
+                    let paused_at = tokio::time::Instant::now();
+                    if !task.wait_for_resume().await {
+                        control.stop();
+                        let _ = (&mut handle).await;
+                        return Ending::Stopped;
+                    }
+
+                    if let Some(deadline) = &mut deadline {
+                        *deadline += paused_at.elapsed();
+                    }
+                    control.resume();
+                }
+                Some(Command::Resume) => {}
+                Some(Command::Stop) | None => {
+                    control.stop();
+                    // Make sure nothing is sent once the clicker reports it has stopped
+                    let _ = (&mut handle).await;
+                    return Ending::Stopped;
+                }
+            },
+        }
+    }
+}
+
+/// Runs a sequence until it ends, hits a limit or is stopped, returning how
+/// it ended
+async fn run_sequence(
+    mut runner: SequenceRunner,
+    config: &ClickerConfig,
+    input_handler: &Arc<Mutex<InputHandler>>,
+    task: &mut Task,
+    mut deadline: Option<tokio::time::Instant>,
+) -> Ending {
+    let click_limit = config.click_limit();
+    let mut click_count = 0u32;
+    let mut next_action = tokio::time::Instant::now();
+    // An action waiting for its time, kept across pauses
+    let mut pending = None;
+    let mut screen = None;
+
+    loop {
+        let action = match pending {
+            Some(action) => action,
+            None => match runner.next_action() {
+                Ok(Some(action)) => {
+                    // Waits are slept through below, so stop and pause are heard
+                    // even in a loop that does nothing but wait
+                    if let Action::Wait(delay) = action {
+                        next_action += delay;
+                    }
+                    pending = Some(action);
+                    action
+                }
+                Ok(None) => return Ending::Completed,
+                Err(e) => return Ending::Failed(e.to_string()),
+            },
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(next_action) => {
+                pending = None;
+                if let Action::Wait(_) = action {
+                    continue;
+                }
+                if let Action::WaitColor(condition) = action {
+                    match color_matches(&mut screen, &condition) {
+                        Ok(true) => next_action = next_action.max(tokio::time::Instant::now()),
+                        Ok(false) => {
+                            pending = Some(action);
+                            next_action = tokio::time::Instant::now() + COLOR_POLL;
+                        }
+                        Err(e) => return Ending::Failed(e),
+                    }
+                    continue;
+                }
+
+                if matches!(action, Action::Click { .. })
+                    && click_limit.is_some_and(|limit| click_count >= limit)
+                {
+                    return Ending::Completed;
+                }
+
+                if let Err(e) = perform_action(input_handler, action) {
+                    return Ending::Failed(e);
+                }
+
+                let scheduled = next_action;
+                // Waits count from when the action finished, not when it was due
+                next_action = next_action.max(tokio::time::Instant::now());
+
+                if matches!(action, Action::Click { .. }) {
+                    click_count += 1;
+                    task.clicked(ClickSample {
+                        count: click_count as u64,
+                        scheduled: scheduled.into_std(),
+                        clicked: Instant::now(),
+                    });
+                }
+            }
+            _ = wait_for_deadline(deadline) => return Ending::Completed,
+            command = task.commands.recv() => match command {
+                Some(Command::Pause) => {
+                    let paused_at = tokio::time::Instant::now();
+                    if !task.wait_for_resume().await {
+                        return Ending::Stopped;
+                    }
+
+                    let paused_for = paused_at.elapsed();
+                    next_action += paused_for;
+                    if let Some(deadline) = &mut deadline {
+                        *deadline += paused_for;
+                    }
+                }
+                Some(Command::Resume) => {}
+                Some(Command::Stop) | None => return Ending::Stopped,
+            },
+        }
+    }
+}
+
+/// Checks a color condition, connecting to the screen the first time
+fn color_matches(screen: &mut Option<Screen>, condition: &PixelCondition) -> Result<bool, String> {
+    let screen = match screen {
+        Some(screen) => screen,
+        None => screen.insert(Screen::connect().map_err(|e| e.to_string())?),
+    };
+    condition
+        .is_met(screen)
+        .map_err(|e| format!("Reading the screen failed: {e}"))
+}
+
+/// Clicks at `target` if given, otherwise where the settings say. A click held
+/// for `hold` is pressed and released separately, so the wait neither blocks the
+/// runtime nor keeps the input handler locked
+async fn perform_click(
+    input_handler: &Arc<Mutex<InputHandler>>,
+    config: &ClickerConfig,
+    target: Option<Coordinates>,
+    hold: Option<Duration>,
+) -> Result<(), String> {
+    let location = target.or(config.use_location.then_some(config.location));
+    let with_handler = |f: &mut dyn FnMut(&mut InputHandler) -> anyhow::Result<()>| {
+        input_handler
+            .lock()
+            .map_err(|e| format!("Lock error: {e}"))
+            .and_then(|mut handler| f(&mut handler).map_err(|e| format!("Click failed: {e}")))
+    };
+
+    let Some(hold) = hold else {
+        return with_handler(&mut |handler| {
+            if let Some(location) = location {
+                handler.click_at(location, config.mouse_button, config.click_action)
+            } else {
+                handler.click(config.mouse_button, config.click_action)
+            }
+        });
+    };
+
+    if let Some(location) = location {
+        with_handler(&mut |handler| handler.move_to(location))?;
+        tokio::time::sleep(MOVE_SETTLE).await;
+    }
+    let presses = match config.click_action {
+        ClickAction::Single => 1,
+        ClickAction::Double => 2,
+    };
+    for press in 0..presses {
+        if press > 0 {
+            tokio::time::sleep(DOUBLE_CLICK_GAP).await;
+        }
+        for pressed in [true, false] {
+            with_handler(&mut |handler| {
+                handler.send(CaptureKind::Button {
+                    button: config.mouse_button,
+                    pressed,
+                })
+            })?;
+            if pressed {
+                tokio::time::sleep(hold).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Looks for the target image on a blocking thread, since matching against a
+/// whole screenshot can take longer than the click interval
+async fn locate_target(
+    mut finder: TargetFinder,
+) -> Result<(TargetFinder, anyhow::Result<Located>), String> {
+    tokio::task::spawn_blocking(move || {
+        let located = finder.locate();
+        (finder, located)
+    })
+    .await
+    .map_err(|e| format!("Image search failed: {e}"))
+}
+
+fn send_input(input_handler: &Arc<Mutex<InputHandler>>, kind: CaptureKind) -> Result<(), String> {
+    input_handler
+        .lock()
+        .map_err(|e| format!("Lock error: {e}"))
+        .and_then(|mut handler| {
+            handler
+                .send(kind)
+                .map_err(|e| format!("Playback failed: {e}"))
+        })
+}
+
+fn perform_action(input_handler: &Arc<Mutex<InputHandler>>, action: Action) -> Result<(), String> {
+    input_handler
+        .lock()
+        .map_err(|e| format!("Lock error: {e}"))
+        .and_then(|mut handler| {
+            match action {
+                Action::Click {
+                    button,
+                    action,
+                    location: Some(location),
+                } => handler.click_at(location, button, action),
+                Action::Click {
+                    button,
+                    action,
+                    location: None,
+                } => handler.click(button, action),
+                Action::Move(location) => handler.move_to(location),
+                Action::Key(code) => handler
+                    .send(CaptureKind::Key {
+                        code,
+                        pressed: true,
+                    })
+                    .and_then(|_| {
+                        handler.send(CaptureKind::Key {
+                            code,
+                            pressed: false,
+                        })
+                    }),
+                Action::Wait(_) | Action::WaitColor(_) => Ok(()),
+            }
+            .map_err(|e| format!("Sequence step failed: {e}"))
+        })
+}
+
+/// Runs a whole run, returning how it ended
+async fn drive(
+    mut config: ClickerConfig,
+    input_handler: Arc<Mutex<InputHandler>>,
+    task: &mut Task,
+    mut live_rx: watch::Receiver<LiveSettings>,
+) -> Ending {
+    let mut starts_at = tokio::time::Instant::now() + config.start_delay;
+
+    // A pause holds the countdown where it is until resumed
+    let start = tokio::time::sleep_until(starts_at);
+    tokio::pin!(start);
+    loop {
+        tokio::select! {
+            _ = &mut start => break,
+            command = task.commands.recv() => match command {
+                Some(Command::Pause) => {
+                    let paused_at = tokio::time::Instant::now();
+                    if !task.wait_for_resume().await {
+                        return Ending::Stopped;
+                    }
+                    starts_at += paused_at.elapsed();
+                    start.as_mut().reset(starts_at);
+                }
+                Some(Command::Resume) => {}
+                Some(Command::Stop) | None => return Ending::Stopped,
+            },
+        }
+    }
+    let mut deadline = config.run_limit.map(|limit| starts_at + limit);
+    task.set_status(Status::Running);
+
+    let mut jitter_gen =
+        Jitter::from_seed(config.seed).with_profile(config.learned_profile.clone());
+
+    if let Some(playback) = config.playback.take() {
+        return play_macro(
+            playback,
+            &config,
+            &input_handler,
+            &mut jitter_gen,
+            task,
+            deadline,
+        )
+        .await;
+    }
+
+    if let Some(script) = config.script.take() {
+        let options = ScriptOptions {
+            runs: config.script_runs,
+            button: config.mouse_button,
+            action: config.click_action,
+            click_limit: config.click_limit(),
+            jitter: config.uses_jitter().then_some((jitter_gen, config.jitter)),
+            seed: config.seed,
+        };
+        return run_script(script, options, &input_handler, task, deadline).await;
+    }
+
+    if let Some(program) = config.program.take() {
+        let runner = SequenceRunner::from_seed(program, config.seed);
+        return run_sequence(runner, &config, &input_handler, task, deadline).await;
+    }
+
+    let screen = match config
+        .pixel_condition
+        .map(|_| Screen::connect())
+        .transpose()
+    {
+        Ok(screen) => screen,
+        Err(e) => return Ending::Failed(e.to_string()),
+    };
+    let finder = config
+        .image_target
+        .clone()
+        .map(|target| TargetFinder::new(target, config.seed))
+        .transpose();
+    let mut finder = match finder {
+        Ok(finder) => finder,
+        Err(e) => return Ending::Failed(e.to_string()),
+    };
+
+    let mut click_count = 0u32;
+    let click_limit = config.click_limit();
+    let mut interval = tokio::time::interval(config.base_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    interval.tick().await;
+    // Shifted forward by every pause so the ramp only sees time spent clicking
+    let mut started = tokio::time::Instant::now();
+    // Kept in step with the interval too, in case jitter is switched on mid-run
+    let mut next_tick = started;
+    // Worked out once per click, so a command arriving mid-wait doesn't add
+    // another delay
+    let mut due = None;
+
+    loop {
+        if live_rx.has_changed().unwrap_or(false) {
+            let live = live_rx.borrow_and_update().clone();
+            if live.base_interval != config.base_interval {
+                interval =
+                    tokio::time::interval_at(next_tick + live.base_interval, live.base_interval);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            }
+            config.apply(&live);
+        }
+
+        if due.is_none() {
+            let base = config.delay_before(click_count, started.elapsed());
+            due = next_due(&config, &mut jitter_gen, next_tick, base);
+        }
+
+        tokio::select! {
+            scheduled = wait_for_tick(&mut interval, due) => {
+                due = None;
+                next_tick = scheduled;
+                if click_limit.is_some_and(|limit| click_count >= limit) {
+                    return Ending::Completed;
+                }
+
+                if let (Some(condition), Some(screen)) = (&config.pixel_condition, &screen) {
+                    match condition.is_met(screen) {
+                        Ok(true) => {}
+                        // Not yet, so try again on the next tick
+                        Ok(false) => continue,
+                        Err(e) => return Ending::Failed(format!("Reading the screen failed: {e}")),
+                    }
+                }
+
+                let mut target = None;
+                if let Some(searching) = finder.take() {
+                    let (searching, located) = match locate_target(searching).await {
+                        Ok(found) => found,
+                        Err(e) => return Ending::Failed(e),
+                    };
+                    let finder = finder.insert(searching);
+                    match located {
+                        Ok(Located::At(location)) => target = Some(location),
+                        Ok(Located::Missed(_)) if finder.on_miss() == MissAction::Skip => {
+                            continue;
+                        }
+                        Ok(Located::Missed(best)) => return Ending::Failed(finder.describe_miss(best)),
+                        Err(e) => return Ending::Failed(format!("Reading the screen failed: {e}")),
+                    }
+                }
+
+                let hold = jitter_gen.next_hold();
+                if let Err(e) = perform_click(&input_handler, &config, target, hold).await {
+                    return Ending::Failed(e);
+                }
+
+                click_count += 1;
+                task.clicked(ClickSample {
+                    count: click_count as u64,
+                    scheduled: scheduled.into_std(),
+                    clicked: Instant::now(),
+                });
+            }
+            _ = wait_for_deadline(deadline) => return Ending::Completed,
+            command = task.commands.recv() => match command {
+                Some(Command::Pause) => {
+                    let paused_at = tokio::time::Instant::now();
+                    if !task.wait_for_resume().await {
+                        return Ending::Stopped;
+                    }
+
+                    // Carry on exactly where we left off, schedule included
+                    let paused_for = paused_at.elapsed();
+                    started += paused_for;
+                    next_tick += paused_for;
+                    if let Some(due) = &mut due {
+                        *due += paused_for;
+                    }
+                    if let Some(deadline) = &mut deadline {
+                        *deadline += paused_for;
+                    }
+                    interval.reset();
+                }
+                Some(Command::Resume) => {}
+                Some(Command::Stop) | None => return Ending::Stopped,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::InputBackend,
+        sequence::{Sequence, Step},
+    };
+
+    /// Counts the clicks it is asked for instead of sending them
+    struct CountingBackend(Arc<Mutex<u32>>);
+
+    impl InputBackend for CountingBackend {
+        fn click(&mut self, _: MouseButton) -> anyhow::Result<()> {
+            *self.0.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        fn press(&mut self, _: MouseButton) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn release(&mut self, _: MouseButton) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn move_to(&mut self, _: Coordinates) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn move_by(&mut self, _: i32, _: i32) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn scroll(&mut self, _: i32, _: i32) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn key(&mut self, _: u16, _: bool) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn counting_input() -> (Arc<Mutex<InputHandler>>, Arc<Mutex<u32>>) {
+        let clicks = Arc::new(Mutex::new(0));
+        let handler = InputHandler::with_backend(CountingBackend(Arc::clone(&clicks)));
+        (Arc::new(Mutex::new(handler)), clicks)
+    }
+
+    #[test]
+    fn the_builder_rejects_rates_it_cant_click_at() {
+        for cps in [
+            0.0,
+            -5.0,
+            f64::NAN,
+            f64::INFINITY,
+            MIN_CPS / 2.0,
+            MAX_CPS * 2.0,
+        ] {
+            let built = ClickerConfig::builder().cps(cps).build();
+            assert!(built.is_err(), "{cps}");
+        }
+        assert!(
+            ClickerConfig::builder()
+                .interval(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+        let ramp = Ramp {
+            start_cps: 0.0,
+            ..Ramp::default()
+        };
+        assert!(ClickerConfig::builder().ramp(ramp).build().is_err());
+    }
+
+    #[test]
+    fn the_last_of_cps_and_interval_wins() {
+        let interval = |builder: ClickerBuilder| builder.build().unwrap().live().base_interval;
+
+        assert_eq!(
+            interval(ClickerConfig::builder().cps(MIN_CPS)),
+            Duration::from_secs(100)
+        );
+        assert_eq!(
+            interval(ClickerConfig::builder().cps(25.0)),
+            Duration::from_millis(40)
+        );
+        assert_eq!(
+            interval(
+                ClickerConfig::builder()
+                    .cps(f64::NAN)
+                    .interval(Duration::from_millis(7))
+            ),
+            Duration::from_millis(7)
+        );
+        assert_eq!(
+            interval(
+                ClickerConfig::builder()
+                    .interval(Duration::ZERO)
+                    .cps(MAX_CPS)
+            ),
+            Duration::from_millis(1)
+        );
+    }
+
+    #[test]
+    fn completes_after_the_click_limit() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, clicks) = counting_input();
+        let config = ClickerConfig::builder()
+            .interval(Duration::from_millis(1))
+            .click_limit(5)
+            .build()
+            .unwrap();
+
+        let mut clicker = Clicker::start(config, input, runtime.handle(), || {});
+        assert_eq!(runtime.block_on(clicker.finished()), Ending::Completed);
+        assert_eq!(*clicks.lock().unwrap(), 5);
+
+        let mut samples = 0;
+        let mut statuses = Vec::new();
+        while let Some(event) = clicker.try_event() {
+            match event {
+                Event::Clicked(sample) => {
+                    samples += 1;
+                    assert_eq!(sample.count, samples);
+                }
+                Event::StatusChanged(status) => statuses.push(status),
+            }
+        }
+        assert_eq!(samples, 5);
+        assert_eq!(
+            statuses,
+            [Status::Running, Status::Ended(Ending::Completed)]
+        );
+    }
+
+    #[test]
+    fn stops_when_told_to_or_dropped() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, _) = counting_input();
+        let config = ClickerConfig::builder()
+            .interval(Duration::from_millis(1))
+            .build()
+            .unwrap();
+
+        let mut clicker =
+            Clicker::start(config.clone(), Arc::clone(&input), runtime.handle(), || {});
+        clicker.stop();
+        assert_eq!(runtime.block_on(clicker.finished()), Ending::Stopped);
+
+        let clicker = Clicker::start(config, input, runtime.handle(), || {});
+        let mut status = clicker.status.clone();
+        drop(clicker);
+        let ended = runtime.block_on(status.wait_for(|status| matches!(status, Status::Ended(_))));
+        assert_eq!(*ended.unwrap(), Status::Ended(Ending::Stopped));
+    }
+
+    #[test]
+    fn stops_a_sequence_that_only_waits() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, _) = counting_input();
+        let program = Sequence {
+            steps: vec![
+                Step::Label {
+                    name: "top".to_owned(),
+                },
+                Step::Wait {
+                    millis: 1,
+                    use_random: false,
+                    max_millis: 1,
+                },
+                Step::Jump {
+                    label: "top".to_owned(),
+                },
+            ],
+        }
+        .compile()
+        .unwrap();
+        let config = ClickerConfig::builder().program(program).build().unwrap();
+
+        let mut clicker = Clicker::start(config, input, runtime.handle(), || {});
+        std::thread::sleep(Duration::from_millis(20));
+        clicker.stop();
+        let ended = runtime.block_on(async {
+            tokio::time::timeout(Duration::from_secs(5), clicker.finished()).await
+        });
+        assert!(matches!(ended, Ok(Ending::Stopped)));
+    }
+
+    #[test]
+    fn refuses_live_updates_it_cant_click_at() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, clicks) = counting_input();
+        let config = ClickerConfig::builder()
+            .interval(Duration::from_millis(1))
+            .click_limit(10)
+            .build()
+            .unwrap();
+        let live = config.live();
+
+        let mut clicker = Clicker::start(config, input, runtime.handle(), || {});
+        let stalled = LiveSettings {
+            base_interval: Duration::ZERO,
+            ..live.clone()
+        };
+        assert!(clicker.update(stalled).is_err());
+        assert!(!clicker.update(live.clone()).unwrap());
+        let slower = LiveSettings {
+            base_interval: Duration::from_millis(2),
+            ..live
+        };
+        assert!(clicker.update(slower).unwrap());
+
+        assert_eq!(runtime.block_on(clicker.finished()), Ending::Completed);
+        assert_eq!(*clicks.lock().unwrap(), 10);
+    }
+
+    #[test]
+    fn pauses_and_resumes() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, clicks) = counting_input();
+        let config = ClickerConfig::builder()
+            .interval(Duration::from_millis(1))
+            .click_limit(20)
+            .build()
+            .unwrap();
+
+        let mut clicker = Clicker::start(config, input, runtime.handle(), || {});
+        runtime.block_on(async {
+            clicker
+                .status
+                .wait_for(|status| *status == Status::Running)
+                .await
+                .unwrap();
+            assert!(clicker.pause());
+            clicker
+                .status
+                .wait_for(|status| *status == Status::Paused)
+                .await
+                .unwrap();
+        });
+
+        let paused_at = *clicks.lock().unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(*clicks.lock().unwrap(), paused_at);
+
+        assert!(clicker.resume());
+        assert_eq!(runtime.block_on(clicker.finished()), Ending::Completed);
+        assert_eq!(*clicks.lock().unwrap(), 20);
+    }
+
+    #[test]
+    fn pausing_holds_the_start_delay() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, clicks) = counting_input();
+        let config = ClickerConfig::builder()
+            .interval(Duration::from_millis(1))
+            .start_delay(Duration::from_millis(20))
+            .click_limit(3)
+            .build()
+            .unwrap();
+
+        let mut clicker = Clicker::start(config, input, runtime.handle(), || {});
+        assert!(clicker.pause());
+        runtime.block_on(async {
+            clicker
+                .status
+                .wait_for(|status| *status == Status::Paused)
+                .await
+                .unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(*clicks.lock().unwrap(), 0);
+        assert_eq!(clicker.status(), Status::Paused);
+
+        assert!(clicker.resume());
+        assert_eq!(runtime.block_on(clicker.finished()), Ending::Completed);
+        assert_eq!(*clicks.lock().unwrap(), 3);
+
+        let mut statuses = Vec::new();
+        while let Some(event) = clicker.try_event() {
+            if let Event::StatusChanged(status) = event {
+                statuses.push(status);
+            }
+        }
+        assert_eq!(
+            statuses,
+            [
+                Status::Paused,
+                Status::Waiting,
+                Status::Running,
+                Status::Ended(Ending::Completed)
+            ]
+        );
+    }
+
+    #[test]
+    fn stray_commands_dont_push_clicks_back() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (input, clicks) = counting_input();
+        // Jitter keeps its own schedule rather than following the interval
+        let config = ClickerConfig::builder()
+            .interval(Duration::from_millis(20))
+            .jitter(1)
+            .build()
+            .unwrap();
+
+        let clicker = Clicker::start(config, input, runtime.handle(), || {});
+        for _ in 0..60 {
+            clicker.resume();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        clicker.stop();
+
+        // About 15 when on time, none when every command delays the next click
+        let clicked = *clicks.lock().unwrap();
+        assert!(clicked >= 8, "{clicked} clicks");
+    }
+}
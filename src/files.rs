@@ -0,0 +1,359 @@
+//! Files for keeping sequences and macros outside the app's own storage.
+//!
+//! Sequences are [RON](https://github.com/ron-rs/ron), meant to be read and
+//! edited by hand:
+//!
+//! ```ron
+//! // Click sequence
+//! (
+//!     version: 1,
+//!     steps: [
+//!         Label(name: "start"),
+//!         Click(button: Left, action: Single, use_location: true, location: (x: 640, y: 360)),
+//!         Wait(millis: 200, use_random: true, max_millis: 500),
+//!         Key(code: 57),
+//!         Jump(label: "start"),
+//!     ],
+//! )
+//! ```
+//!
+//! Macros are JSON Lines: a header line, then one event per line with the
+//! microseconds since the previous event and what happened:
+//!
+//! ```json
+//! {"format":"click-macro","version":1}
+//! {"delay_us":0,"Button":{"button":"Left","pressed":true}}
+//! {"delay_us":84211,"Button":{"button":"Left","pressed":false}}
+//! {"delay_us":30125,"Move":{"dx":4,"dy":-2}}
+//! {"delay_us":501433,"Key":{"code":30,"pressed":true}}
+//! ```
+//!
+//! Both carry a format version, and files from a newer version are refused
+//! rather than half read.
+
+use std::{fs, time::Duration};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::CaptureKind,
+    macros::{Macro, MacroEvent},
+    sequence::{Sequence, Step},
+};
+
+/// Format version written to new files
+pub const VERSION: u32 = 1;
+
+pub const SEQUENCE_EXTENSION: &str = "ron";
+pub const MACRO_EXTENSION: &str = "jsonl";
+
+const SEQUENCE_COMMENT: &str = "// Click sequence\n";
+const MACRO_FORMAT: &str = "click-macro";
+
+#[derive(Deserialize, Serialize)]
+struct SequenceFile {
+    version: u32,
+    steps: Vec<Step>,
+}
+
+/// Just the version, read before the rest so newer files get a clear error
+#[derive(Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MacroHeader {
+    format: String,
+    version: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+struct MacroLine {
+    delay_us: u64,
+    #[serde(flatten)]
+    kind: CaptureKind,
+}
+
+/// Puts the line number of a JSON Lines file in place of the one serde_json
+/// reports, which only ever counts within the line
+fn json_error(number: usize, e: serde_json::Error) -> anyhow::Error {
+    let message = e.to_string();
+    let position = format!(" at line {} column {}", e.line(), e.column());
+    let message = message.strip_suffix(&position).unwrap_or(&message);
+    anyhow!("Line {number}, column {}: {message}", e.column())
+}
+
+fn check_version(version: u32) -> Result<()> {
+    if version > VERSION {
+        bail!(
+            "The file uses format version {version}, but this version of Click only reads up to {VERSION}"
+        );
+    }
+    Ok(())
+}
+
+fn ron_error(e: ron::error::SpannedError) -> anyhow::Error {
+    anyhow!(
+        "Line {}, column {}: {}",
+        e.span.start.line,
+        e.span.start.col,
+        e.code
+    )
+}
+
+pub fn sequence_to_string(sequence: &Sequence) -> Result<String> {
+    let file = SequenceFile {
+        version: VERSION,
+        steps: sequence.steps.clone(),
+    };
+    // One step per line
+    let config = ron::ser::PrettyConfig::new()
+        .indentor("    ")
+        .depth_limit(2);
+    let body = ron::ser::to_string_pretty(&file, config)?;
+    Ok(format!("{SEQUENCE_COMMENT}{body}\n"))
+}
+
+/// Reads a sequence file, refusing one that wouldn't run, such as a jump to an
+/// unknown label or an unbalanced repeat
+pub fn sequence_from_str(text: &str) -> Result<Sequence> {
+    let Versioned { version } = ron::from_str(text).map_err(ron_error)?;
+    check_version(version)?;
+
+    let file: SequenceFile = ron::from_str(text).map_err(ron_error)?;
+    let sequence = Sequence { steps: file.steps };
+    sequence.compile()?;
+    Ok(sequence)
+}
+
+pub fn macro_to_string(recording: &Macro) -> Result<String> {
+    let mut text = serde_json::to_string(&MacroHeader {
+        format: MACRO_FORMAT.to_owned(),
+        version: VERSION,
+    })?;
+    text.push('\n');
+
+    for event in &recording.events {
+        text.push_str(&serde_json::to_string(&MacroLine {
+            delay_us: event.delay.as_micros() as u64,
+            kind: event.kind,
+        })?);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+pub fn macro_from_str(text: &str) -> Result<Macro> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let Some((number, header)) = lines.next() else {
+        bail!("The file is empty");
+    };
+    let header: MacroHeader = serde_json::from_str(header)
+        .map_err(|e| anyhow!("Line {number}: not a Click macro header ({e})"))?;
+    if header.format != MACRO_FORMAT {
+        bail!(
+            "Line {number}: expected format \"{MACRO_FORMAT}\", found \"{}\"",
+            header.format
+        );
+    }
+    check_version(header.version)?;
+
+    let events = lines
+        .map(|(number, line)| {
+            let line: MacroLine = serde_json::from_str(line).map_err(|e| json_error(number, e))?;
+            Ok(MacroEvent {
+                delay: Duration::from_micros(line.delay_us),
+                kind: line.kind,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Macro { events })
+}
+
+/// Asks for a file to open and reads it with `parse`, returning `None` if the
+/// dialog was cancelled
+pub fn open_dialog<T>(
+    description: &str,
+    extensions: &[&str],
+    parse: impl FnOnce(&str) -> Result<T>,
+) -> Result<Option<T>> {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(description, extensions)
+        .add_filter("All files", &["*"])
+        .pick_file()
+    else {
+        return Ok(None);
+    };
+
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Couldn't read {}", path.display()))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    parse(&text).map(Some).map_err(|e| anyhow!("{name}: {e}"))
+}
+
+/// Asks where to save `text`, returning false if the dialog was cancelled
+pub fn save_dialog(
+    description: &str,
+    extension: &str,
+    file_name: &str,
+    text: &str,
+) -> Result<bool> {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter(description, &[extension])
+        .set_file_name(format!("{file_name}.{extension}"))
+        .save_file()
+    else {
+        return Ok(false);
+    };
+
+    fs::write(&path, text).with_context(|| format!("Couldn't write {}", path.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{ClickAction, Coordinates, MouseButton};
+
+    fn sequence() -> Sequence {
+        Sequence {
+            steps: vec![
+                Step::Label {
+                    name: "start".to_owned(),
+                },
+                Step::Click {
+                    button: MouseButton::Left,
+                    action: ClickAction::Double,
+                    use_location: true,
+                    location: Coordinates { x: 640, y: 360 },
+                },
+                Step::Wait {
+                    millis: 200,
+                    use_random: true,
+                    max_millis: 500,
+                },
+                Step::Repeat { times: 3 },
+                Step::Key { code: 57 },
+                Step::EndRepeat,
+                Step::Jump {
+                    label: "start".to_owned(),
+                },
+            ],
+        }
+    }
+
+    fn recording() -> Macro {
+        let event = |delay_us, kind| MacroEvent {
+            delay: Duration::from_micros(delay_us),
+            kind,
+        };
+        Macro {
+            events: vec![
+                event(
+                    0,
+                    CaptureKind::Button {
+                        button: MouseButton::Left,
+                        pressed: true,
+                    },
+                ),
+                event(
+                    84_211,
+                    CaptureKind::Button {
+                        button: MouseButton::Left,
+                        pressed: false,
+                    },
+                ),
+                event(30_125, CaptureKind::Move { dx: 4, dy: -2 }),
+                event(12, CaptureKind::MoveTo { x: 100, y: 200 }),
+                event(
+                    7_000,
+                    CaptureKind::Wheel {
+                        vertical: -1,
+                        horizontal: 0,
+                    },
+                ),
+                event(
+                    501_433,
+                    CaptureKind::Key {
+                        code: 30,
+                        pressed: true,
+                    },
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn sequences_round_trip() {
+        let text = sequence_to_string(&sequence()).unwrap();
+        assert!(text.starts_with(SEQUENCE_COMMENT));
+        assert_eq!(sequence_from_str(&text).unwrap(), sequence());
+    }
+
+    #[test]
+    fn sequences_from_newer_versions_are_refused() {
+        let text = sequence_to_string(&sequence())
+            .unwrap()
+            .replace("version: 1", "version: 2");
+        let error = sequence_from_str(&text).unwrap_err();
+        assert!(error.to_string().contains("format version 2"), "{error}");
+    }
+
+    #[test]
+    fn broken_sequences_are_reported_on_open() {
+        let error =
+            sequence_from_str("(\n    version: 1,\n    steps: [Wait(millis: )],\n)").unwrap_err();
+        assert!(error.to_string().starts_with("Line 3, column"), "{error}");
+
+        let error =
+            sequence_from_str("(version: 1, steps: [Jump(label: \"nowhere\")])").unwrap_err();
+        assert_eq!(error.to_string(), "Step 1: no label called \"nowhere\"");
+
+        let error = sequence_from_str("(version: 1, steps: [EndRepeat])").unwrap_err();
+        assert_eq!(error.to_string(), "Step 1: End repeat without a Repeat");
+    }
+
+    #[test]
+    fn macros_round_trip() {
+        let text = macro_to_string(&recording()).unwrap();
+        assert_eq!(text.lines().count(), recording().events.len() + 1);
+        assert_eq!(macro_from_str(&text).unwrap(), recording());
+    }
+
+    #[test]
+    fn macros_from_newer_versions_are_refused() {
+        let text = macro_to_string(&recording())
+            .unwrap()
+            .replace("\"version\":1", "\"version\":2");
+        let error = macro_from_str(&text).unwrap_err();
+        assert!(error.to_string().contains("format version 2"), "{error}");
+    }
+
+    #[test]
+    fn malformed_macro_lines_are_reported_with_their_number() {
+        let text = "{\"format\":\"click-macro\",\"version\":1}\n\
+                    {\"delay_us\":0,\"Move\":{\"dx\":1,\"dy\":1}}\n\
+                    \n\
+                    {\"delay_us\":10,\"Move\":{\"dx\":1}}\n";
+        let error = macro_from_str(text).unwrap_err();
+        assert!(error.to_string().starts_with("Line 4, column"), "{error}");
+
+        let error = macro_from_str("{\"format\":\"other\",\"version\":1}").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 1: expected format \"click-macro\", found \"other\""
+        );
+        assert_eq!(
+            macro_from_str("\n\n").unwrap_err().to_string(),
+            "The file is empty"
+        );
+    }
+}
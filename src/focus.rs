@@ -0,0 +1,461 @@
+//! Following which window has focus, so click settings can change with the
+//! application being clicked in
+
+use std::fmt;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// The window that has focus, as the window manager describes it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FocusedWindow {
+    /// Application name from `WM_CLASS`, such as `firefox`
+    pub instance: String,
+    /// Application class from `WM_CLASS`, such as `Firefox`
+    pub class: String,
+    pub title: String,
+}
+
+/// Which part of the window a rule looks at
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum RuleField {
+    Class,
+    Title,
+}
+
+impl fmt::Display for RuleField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleField::Class => write!(f, "Class"),
+            RuleField::Title => write!(f, "Title"),
+        }
+    }
+}
+
+impl RuleField {
+    pub fn all() -> [RuleField; 2] {
+        [RuleField::Class, RuleField::Title]
+    }
+}
+
+/// Switches to a profile while a matching window has focus
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppRule {
+    pub field: RuleField,
+    /// Text the field has to contain, ignoring case
+    pub pattern: String,
+    /// Name of the profile whose settings apply
+    pub profile: String,
+}
+
+impl Default for AppRule {
+    fn default() -> Self {
+        Self {
+            field: RuleField::Class,
+            pattern: String::new(),
+            profile: String::new(),
+        }
+    }
+}
+
+impl AppRule {
+    /// A rule matching `window` by its class
+    pub fn for_window(window: &FocusedWindow, profile: String) -> Self {
+        let pattern = if window.class.is_empty() {
+            &window.instance
+        } else {
+            &window.class
+        };
+        Self {
+            field: RuleField::Class,
+            pattern: pattern.clone(),
+            profile,
+        }
+    }
+
+    pub fn matches(&self, window: &FocusedWindow) -> bool {
+        let pattern = self.pattern.trim().to_lowercase();
+        if pattern.is_empty() {
+            return false;
+        }
+        let contains = |text: &str| text.to_lowercase().contains(&pattern);
+        match self.field {
+            RuleField::Class => contains(&window.class) || contains(&window.instance),
+            RuleField::Title => contains(&window.title),
+        }
+    }
+}
+
+/// Watches the focused window on a background thread until dropped
+pub struct FocusWatcher {
+    platform: platform::FocusWatcher,
+}
+
+impl FocusWatcher {
+    /// Starts watching, calling `on_change` from the watcher thread whenever
+    /// the focused window or its title changes
+    pub fn start(on_change: impl Fn() + Send + 'static) -> Result<Self> {
+        Ok(Self {
+            platform: platform::FocusWatcher::start(Box::new(on_change))?,
+        })
+    }
+
+    /// Returns the focused window if it changed since the last call, with
+    /// `Some(None)` when no window has focus
+    pub fn changed(&self) -> Result<Option<Option<FocusedWindow>>> {
+        self.platform.changed()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::{
+        sync::mpsc::{self, Receiver, TryRecvError},
+        thread,
+    };
+
+    use anyhow::{Result, anyhow};
+    use x11rb::{
+        connection::Connection,
+        protocol::{
+            Event,
+            xproto::{AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window},
+        },
+        rust_connection::RustConnection,
+    };
+
+    use super::FocusedWindow;
+
+    x11rb::atom_manager! {
+        Atoms: AtomsCookie {
+            _NET_ACTIVE_WINDOW,
+            _NET_WM_NAME,
+        }
+    }
+
+    type Update = Result<Option<FocusedWindow>, String>;
+
+    /// Follows EWMH's `_NET_ACTIVE_WINDOW` on the root window, which window
+    /// managers keep up to date, so it also works under XWayland for X11 windows
+    pub struct FocusWatcher {
+        updates: Receiver<Update>,
+    }
+
+    impl FocusWatcher {
+        pub fn start(on_change: Box<dyn Fn() + Send>) -> Result<Self> {
+            let (connection, screen_num) = x11rb::connect(None)
+                .map_err(|e| anyhow!("Couldn't connect to the X server to follow focus: {e}"))?;
+            let root = connection.setup().roots[screen_num].root;
+            let atoms = Atoms::new(&connection)?.reply()?;
+            connection.change_window_attributes(
+                root,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )?;
+            connection.flush()?;
+
+            let (sender, updates) = mpsc::channel();
+            thread::spawn(move || {
+                let watcher = Watcher {
+                    connection,
+                    root,
+                    atoms,
+                    active: None,
+                };
+                // Runs until the connection fails or the watcher is dropped
+                let error = watcher.run(|update| {
+                    let sent = sender.send(Ok(update)).is_ok();
+                    on_change();
+                    sent
+                });
+                let _ = sender.send(Err(format!("Lost track of the focused window: {error}")));
+                on_change();
+            });
+
+            Ok(Self { updates })
+        }
+
+        pub fn changed(&self) -> Result<Option<Option<FocusedWindow>>> {
+            let mut latest = None;
+            loop {
+                match self.updates.try_recv() {
+                    Ok(Ok(window)) => latest = Some(window),
+                    Ok(Err(e)) => return Err(anyhow!(e)),
+                    Err(TryRecvError::Empty) => return Ok(latest),
+                    Err(TryRecvError::Disconnected) => {
+                        return Err(anyhow!("The focus watcher stopped"));
+                    }
+                }
+            }
+        }
+    }
+
+    struct Watcher {
+        connection: RustConnection,
+        root: Window,
+        atoms: Atoms,
+        /// Focused window, whose title changes are listened to as well
+        active: Option<Window>,
+    }
+
+    impl Watcher {
+        /// Reports the focused window and every change to it, until `report`
+        /// returns false or the connection fails
+        fn run(mut self, mut report: impl FnMut(Option<FocusedWindow>) -> bool) -> anyhow::Error {
+            let mut last = None;
+            let mut refresh = true;
+            loop {
+                if refresh {
+                    match self.focused() {
+                        Ok(focused) if focused != last => {
+                            if !report(focused.clone()) {
+                                return anyhow!("The watcher was dropped");
+                            }
+                            last = focused;
+                        }
+                        Ok(_) => {}
+                        Err(e) => return e,
+                    }
+                }
+
+                refresh = match self.connection.wait_for_event() {
+                    Ok(Event::PropertyNotify(event)) => {
+                        (event.window == self.root && event.atom == self.atoms._NET_ACTIVE_WINDOW)
+                            || (Some(event.window) == self.active
+                                && (event.atom == self.atoms._NET_WM_NAME
+                                    || event.atom == u32::from(AtomEnum::WM_NAME)))
+                    }
+                    // The focused window went away before we could read it
+                    Ok(Event::Error(_)) => true,
+                    Ok(_) => false,
+                    Err(e) => return e.into(),
+                };
+            }
+        }
+
+        /// Reads the focused window, and listens for its title changing
+        fn focused(&mut self) -> Result<Option<FocusedWindow>> {
+            let active = self
+                .connection
+                .get_property(
+                    false,
+                    self.root,
+                    self.atoms._NET_ACTIVE_WINDOW,
+                    AtomEnum::WINDOW,
+                    0,
+                    1,
+                )?
+                .reply()?
+                .value32()
+                .and_then(|mut windows| windows.next())
+                .filter(|&window| window != x11rb::NONE);
+
+            if active != self.active {
+                // Either window may already be gone, which only means there's nothing to listen to
+                if let Some(old) = self.active {
+                    let _ = self.connection.change_window_attributes(
+                        old,
+                        &ChangeWindowAttributesAux::new().event_mask(EventMask::NO_EVENT),
+                    );
+                }
+                if let Some(new) = active {
+                    let _ = self.connection.change_window_attributes(
+                        new,
+                        &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                    );
+                }
+                self.connection.flush()?;
+                self.active = active;
+            }
+
+            let Some(window) = active else {
+                return Ok(None);
+            };
+            let (instance, class) = match self.text(window, AtomEnum::WM_CLASS.into()) {
+                Some(wm_class) => {
+                    let mut parts = wm_class.split('\0');
+                    let instance = parts.next().unwrap_or_default().to_owned();
+                    let class = parts.next().unwrap_or_default().to_owned();
+                    (instance, class)
+                }
+                None => Default::default(),
+            };
+            let title = self
+                .text(window, self.atoms._NET_WM_NAME)
+                .or_else(|| self.text(window, AtomEnum::WM_NAME.into()))
+                .unwrap_or_default();
+
+            Ok(Some(FocusedWindow {
+                instance,
+                class,
+                title,
+            }))
+        }
+
+        /// Reads a text property, or `None` if the window doesn't have it or is gone
+        fn text(&self, window: Window, property: u32) -> Option<String> {
+            let reply = self
+                .connection
+                .get_property(false, window, property, AtomEnum::ANY, 0, 1024)
+                .ok()?
+                .reply()
+                .ok()?;
+            if reply.value.is_empty() {
+                return None;
+            }
+            let text = String::from_utf8_lossy(&reply.value);
+            Some(text.trim_end_matches('\0').to_owned())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use anyhow::{Result, bail};
+
+    use super::FocusedWindow;
+
+    /// Can't be created, as following focus only works on X11 so far
+    pub enum FocusWatcher {}
+
+    impl FocusWatcher {
+        pub fn start(_on_change: Box<dyn Fn() + Send>) -> Result<Self> {
+            bail!("Following the focused window is only supported on Linux (X11) for now")
+        }
+
+        pub fn changed(&self) -> Result<Option<Option<FocusedWindow>>> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(instance: &str, class: &str, title: &str) -> FocusedWindow {
+        FocusedWindow {
+            instance: instance.to_owned(),
+            class: class.to_owned(),
+            title: title.to_owned(),
+        }
+    }
+
+    #[test]
+    fn rules_match_part_of_the_field_ignoring_case() {
+        let firefox = window("Navigator", "firefox", "Cookie Clicker – Mozilla Firefox");
+        let rule = |field, pattern: &str| AppRule {
+            field,
+            pattern: pattern.to_owned(),
+            profile: String::new(),
+        };
+
+        assert!(rule(RuleField::Class, "Firefox").matches(&firefox));
+        assert!(rule(RuleField::Class, "navigator").matches(&firefox));
+        assert!(rule(RuleField::Title, "cookie clicker").matches(&firefox));
+        assert!(!rule(RuleField::Title, "Navigator").matches(&firefox));
+        assert!(!rule(RuleField::Class, "chromium").matches(&firefox));
+        assert!(!rule(RuleField::Class, "  ").matches(&firefox));
+    }
+
+    /// Plays the window manager's part by setting `_NET_ACTIVE_WINDOW` itself
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs an X server, run with `xvfb-run cargo test -- --ignored`"]
+    fn follows_the_active_window() {
+        use std::{
+            thread,
+            time::{Duration, Instant},
+        };
+
+        use x11rb::{
+            connection::Connection,
+            protocol::xproto::{AtomEnum, ConnectionExt, CreateWindowAux, PropMode, WindowClass},
+            wrapper::ConnectionExt as _,
+        };
+
+        let (connection, screen_num) = x11rb::connect(None).unwrap();
+        let screen = &connection.setup().roots[screen_num];
+        let root = screen.root;
+        let active_atom = connection
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+            .unwrap()
+            .reply()
+            .unwrap()
+            .atom;
+
+        let target = connection.generate_id().unwrap();
+        connection
+            .create_window(
+                screen.root_depth,
+                target,
+                root,
+                0,
+                0,
+                100,
+                100,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new(),
+            )
+            .unwrap();
+        connection
+            .change_property8(
+                PropMode::REPLACE,
+                target,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                b"game\0Game\0",
+            )
+            .unwrap();
+        connection
+            .change_property8(
+                PropMode::REPLACE,
+                target,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                b"Level 1",
+            )
+            .unwrap();
+        connection.flush().unwrap();
+
+        let watcher = FocusWatcher::start(|| {}).unwrap();
+        let wait_for = |expected: &FocusedWindow| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while Instant::now() < deadline {
+                if let Some(focused) = watcher.changed().unwrap()
+                    && focused.as_ref() == Some(expected)
+                {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            panic!("never saw {expected:?}");
+        };
+
+        connection
+            .change_property32(
+                PropMode::REPLACE,
+                root,
+                active_atom,
+                AtomEnum::WINDOW,
+                &[target],
+            )
+            .unwrap();
+        connection.flush().unwrap();
+        wait_for(&window("game", "Game", "Level 1"));
+
+        connection
+            .change_property8(
+                PropMode::REPLACE,
+                target,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                b"Level 2",
+            )
+            .unwrap();
+        connection.flush().unwrap();
+        wait_for(&window("game", "Game", "Level 2"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Clicking from the command line without opening the window, for shell
+//! scripts and window manager keybinds
+
+use std::{
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::{
+    InputHandler,
+    engine::{Clicker, ClickerConfig, Ending, MAX_CPS, MIN_CPS},
+    input::{ClickAction, Coordinates, MouseButton},
+};
+
+/// Exit code when a run fails or can't start
+const FAILED: u8 = 1;
+/// Exit code when a run is stopped with Ctrl-C, as shells report for SIGINT
+const INTERRUPTED: u8 = 130;
+
+/// Settings for `click run`, named after the ones in the window
+#[derive(clap::Args)]
+pub struct RunArgs {
+    /// Clicks per second
+    #[arg(long, default_value_t = 20.0, value_parser = parse_cps)]
+    cps: f64,
+    /// Milliseconds between clicks, in place of --cps
+    #[arg(long, conflicts_with = "cps")]
+    interval: Option<u64>,
+    /// Mouse button to click: left, right or middle
+    #[arg(long, default_value = "left", value_parser = parse_button)]
+    button: MouseButton,
+    /// Double click instead of single clicks
+    #[arg(long)]
+    double: bool,
+    /// Stop after this many clicks
+    #[arg(long)]
+    count: Option<u32>,
+    /// Stop after this many seconds
+    #[arg(long)]
+    duration: Option<f64>,
+    /// Vary the timing by about this many milliseconds
+    #[arg(long)]
+    jitter: Option<u16>,
+    /// Seed for the jitter, to repeat an earlier run
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Click at X,Y instead of wherever the pointer is
+    #[arg(long, value_name = "X,Y", value_parser = parse_coordinates)]
+    at: Option<Coordinates>,
+    /// Wait this many seconds before the first click
+    #[arg(long, default_value_t = 0.0)]
+    delay: f64,
+}
+
+fn parse_cps(text: &str) -> Result<f64> {
+    let cps: f64 = text.parse()?;
+    if !(MIN_CPS..=MAX_CPS).contains(&cps) {
+        bail!("must be between {MIN_CPS} and {MAX_CPS}");
+    }
+    Ok(cps)
+}
+
+fn parse_button(text: &str) -> Result<MouseButton> {
+    MouseButton::all()
+        .into_iter()
+        .find(|button| button.to_string().eq_ignore_ascii_case(text))
+        .ok_or_else(|| anyhow!("expected left, right or middle"))
+}
+
+fn parse_coordinates(text: &str) -> Result<Coordinates> {
+    let (x, y) = text.split_once(',').context("expected X,Y")?;
+    Ok(Coordinates {
+        x: x.trim().parse()?,
+        y: y.trim().parse()?,
+    })
+}
+
+fn seconds(value: f64, name: &str) -> Result<Duration> {
+    Duration::try_from_secs_f64(value).map_err(|_| anyhow!("--{name} must be a positive number"))
+}
+
+impl RunArgs {
+    fn config(&self) -> Result<ClickerConfig> {
+        let mut builder = match self.interval {
+            Some(milliseconds) => {
+                ClickerConfig::builder().interval(Duration::from_millis(milliseconds))
+            }
+            None => ClickerConfig::builder().cps(self.cps),
+        };
+        builder = builder
+            .button(self.button)
+            .start_delay(seconds(self.delay, "delay")?);
+        if self.double {
+            builder = builder.action(ClickAction::Double);
+        }
+        if let Some(jitter) = self.jitter {
+            builder = builder.jitter(jitter);
+        }
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(location) = self.at {
+            builder = builder.location(location);
+        }
+        if let Some(count) = self.count {
+            builder = builder.click_limit(count);
+        }
+        if let Some(duration) = self.duration {
+            builder = builder.run_limit(seconds(duration, "duration")?);
+        }
+        builder.build()
+    }
+}
+
+/// Clicks until the run completes, fails or Ctrl-C is pressed, returning the
+/// exit code to report
+pub fn run(args: &RunArgs) -> ExitCode {
+    match run_clicker(args) {
+        Ok(Ending::Completed) => ExitCode::SUCCESS,
+        Ok(Ending::Stopped) => ExitCode::from(INTERRUPTED),
+        Ok(Ending::Failed(e)) => {
+            eprintln!("click: {e}");
+            ExitCode::from(FAILED)
+        }
+        Err(e) => {
+            eprintln!("click: {e:#}");
+            ExitCode::from(FAILED)
+        }
+    }
+}
+
+fn run_clicker(args: &RunArgs) -> Result<Ending> {
+    let config = args.config()?;
+    let input = InputHandler::new().context("Failed to create input handler")?;
+    let runtime = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+
+    let mut clicker = Clicker::start(config, Arc::new(Mutex::new(input)), runtime.handle(), || {});
+    runtime.block_on(async move {
+        tokio::select! {
+            ending = clicker.finished() => Ok(ending),
+            signal = tokio::signal::ctrl_c() => {
+                signal.context("Couldn't listen for Ctrl-C")?;
+                clicker.stop();
+                // Let the clicker release anything it holds before exiting
+                Ok(clicker.finished().await)
+            }
+        }
+    })
+}
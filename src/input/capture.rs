@@ -0,0 +1,265 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread::JoinHandle,
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::MouseButton;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum CaptureKind {
+    Button {
+        button: MouseButton,
+        pressed: bool,
+    },
+    /// Relative pointer motion, combined per device report
+    Move {
+        dx: i32,
+        dy: i32,
+    },
+    /// Absolute pointer position in screen pixels, as reported by tablets and
+    /// touchscreens in imported recordings
+    MoveTo {
+        x: i32,
+        y: i32,
+    },
+    /// Wheel notches, positive being up and right
+    Wheel {
+        vertical: i32,
+        horizontal: i32,
+    },
+    /// Keyboard key identified by its Linux input event code
+    Key {
+        code: u16,
+        pressed: bool,
+    },
+}
+
+/// Which physical devices an [`InputCapture`] listens to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureDevices {
+    Mice,
+    MiceAndKeyboards,
+}
+
+/// A physical input event together with the kernel timestamp it was reported at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CapturedEvent {
+    pub time: SystemTime,
+    pub kind: CaptureKind,
+}
+
+/// Listens to physical input devices on a background thread until dropped
+pub struct InputCapture {
+    receiver: mpsc::Receiver<CapturedEvent>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputCapture {
+    pub fn start(devices: CaptureDevices) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = platform::spawn(devices, sender, Arc::clone(&stop))?;
+
+        Ok(Self {
+            receiver,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns every event captured since the last call without blocking
+    pub fn drain(&self) -> impl Iterator<Item = CapturedEvent> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+impl Drop for InputCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        thread::{self, JoinHandle},
+        time::Duration,
+    };
+
+    use anyhow::{Result, bail};
+    use evdev::{Device, EventSummary, KeyCode, RelativeAxisCode, SynchronizationCode};
+
+    use super::{CaptureDevices, CaptureKind, CapturedEvent};
+    use crate::input::{MouseButton, linux::VIRTUAL_DEVICE_NAME};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    fn key_to_button(key: KeyCode) -> Option<MouseButton> {
+        match key {
+            KeyCode::BTN_LEFT => Some(MouseButton::Left),
+            KeyCode::BTN_RIGHT => Some(MouseButton::Right),
+            KeyCode::BTN_MIDDLE => Some(MouseButton::Middle),
+            _ => None,
+        }
+    }
+
+    /// Codes below this are keyboard keys, the rest are buttons
+    const FIRST_BUTTON: u16 = 0x100;
+
+    fn key_to_kind(key: KeyCode, pressed: bool) -> Option<CaptureKind> {
+        if key.code() < FIRST_BUTTON {
+            return Some(CaptureKind::Key {
+                code: key.code(),
+                pressed,
+            });
+        }
+        key_to_button(key).map(|button| CaptureKind::Button { button, pressed })
+    }
+
+    fn open_devices(wanted: CaptureDevices) -> Result<Vec<Device>> {
+        let devices: Vec<Device> = evdev::enumerate()
+            .map(|(_, device)| device)
+            .filter(|device| device.name() != Some(VIRTUAL_DEVICE_NAME))
+            .filter(|device| {
+                device.supported_keys().is_some_and(|keys| {
+                    keys.contains(KeyCode::BTN_LEFT)
+                        || (wanted == CaptureDevices::MiceAndKeyboards
+                            && keys.contains(KeyCode::KEY_A))
+                })
+            })
+            .collect();
+
+        if devices.is_empty() {
+            bail!(
+                "No readable input device found in /dev/input (is your user in the input group?)"
+            );
+        }
+
+        for device in &devices {
+            device.set_nonblocking(true)?;
+        }
+
+        Ok(devices)
+    }
+
+    /// Motion and wheel deltas of a device, held until its next sync report
+    #[derive(Default)]
+    struct Pending {
+        dx: i32,
+        dy: i32,
+        vertical: i32,
+        horizontal: i32,
+    }
+
+    impl Pending {
+        fn take(&mut self) -> impl Iterator<Item = CaptureKind> {
+            let Pending {
+                dx,
+                dy,
+                vertical,
+                horizontal,
+            } = std::mem::take(self);
+            let moved = (dx != 0 || dy != 0).then_some(CaptureKind::Move { dx, dy });
+            let scrolled = (vertical != 0 || horizontal != 0).then_some(CaptureKind::Wheel {
+                vertical,
+                horizontal,
+            });
+            moved.into_iter().chain(scrolled)
+        }
+    }
+
+    pub fn spawn(
+        wanted: CaptureDevices,
+        sender: mpsc::Sender<CapturedEvent>,
+        stop: Arc<AtomicBool>,
+    ) -> Result<JoinHandle<()>> {
+        let mut devices: Vec<(Device, Pending)> = open_devices(wanted)?
+            .into_iter()
+            .map(|device| (device, Pending::default()))
+            .collect();
+
+        Ok(thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                for (device, pending) in &mut devices {
+                    // WouldBlock just means nothing is pending, and an unplugged
+                    // device keeps erroring, so both are simply skipped
+                    let Ok(events) = device.fetch_events() else {
+                        continue;
+                    };
+
+                    for event in events {
+                        let time = event.timestamp();
+                        let kinds: Vec<CaptureKind> = match event.destructure() {
+                            // Value 2 is autorepeat, which isn't a separate press
+                            EventSummary::Key(_, key, value @ (0 | 1)) => {
+                                key_to_kind(key, value == 1).into_iter().collect()
+                            }
+                            EventSummary::RelativeAxis(_, axis, value) => {
+                                match axis {
+                                    RelativeAxisCode::REL_X => pending.dx += value,
+                                    RelativeAxisCode::REL_Y => pending.dy += value,
+                                    // evdev reports wheel up as positive already
+                                    RelativeAxisCode::REL_WHEEL => pending.vertical += value,
+                                    RelativeAxisCode::REL_HWHEEL => pending.horizontal += value,
+                                    _ => {}
+                                }
+                                continue;
+                            }
+                            EventSummary::Synchronization(
+                                _,
+                                SynchronizationCode::SYN_REPORT,
+                                _,
+                            ) => pending.take().collect(),
+                            _ => continue,
+                        };
+
+                        for kind in kinds {
+                            if sender.send(CapturedEvent { time, kind }).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+
+                thread::sleep(POLL_INTERVAL);
+            }
+        }))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use std::{
+        sync::{Arc, atomic::AtomicBool, mpsc},
+        thread::JoinHandle,
+    };
+
+    use anyhow::{Result, bail};
+
+    use super::{CaptureDevices, CapturedEvent};
+
+    pub fn spawn(
+        _wanted: CaptureDevices,
+        _sender: mpsc::Sender<CapturedEvent>,
+        _stop: Arc<AtomicBool>,
+    ) -> Result<JoinHandle<()>> {
+        bail!("Capturing physical input is only supported on Linux")
+    }
+}
@@ -6,25 +6,31 @@ use evdev::{
     AttributeSet, EventType, InputEvent, KeyCode, RelativeAxisCode, uinput::VirtualDevice,
 };
 
+/// Name of our uinput device, so capture can tell our own events from physical ones
+pub const VIRTUAL_DEVICE_NAME: &str = "click-virtual-device";
+
 pub struct PlatformInput {
     device: VirtualDevice,
 }
 
 impl PlatformInput {
     pub fn new() -> Result<Self> {
-        let keys = AttributeSet::<KeyCode>::from_iter([
+        // Every keyboard key, so recorded macros can be replayed
+        let keys = AttributeSet::<KeyCode>::from_iter((1..0x100).map(KeyCode).chain([
             KeyCode::BTN_LEFT,
             KeyCode::BTN_RIGHT,
             KeyCode::BTN_MIDDLE,
-        ]);
+        ]));
 
         let axes = AttributeSet::<RelativeAxisCode>::from_iter([
             RelativeAxisCode::REL_X,
             RelativeAxisCode::REL_Y,
+            RelativeAxisCode::REL_WHEEL,
+            RelativeAxisCode::REL_HWHEEL,
         ]);
 
         let device = VirtualDevice::builder()?
-            .name("click-virtual-device")
+            .name(VIRTUAL_DEVICE_NAME)
             .with_keys(&keys)?
             .with_relative_axes(&axes)?
             .build()?;
@@ -53,6 +59,20 @@ impl InputBackend for PlatformInput {
         Ok(())
     }
 
+    fn press(&mut self, button: MouseButton) -> Result<()> {
+        let key = self.button_to_key(button);
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY.0, key.code(), 1)])?;
+        Ok(())
+    }
+
+    fn release(&mut self, button: MouseButton) -> Result<()> {
+        let key = self.button_to_key(button);
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY.0, key.code(), 0)])?;
+        Ok(())
+    }
+
     fn move_to(&mut self, coords: Coordinates) -> Result<()> {
         self.device.emit(&[
             InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, -32767),
@@ -66,4 +86,34 @@ impl InputBackend for PlatformInput {
 
         Ok(())
     }
+
+    fn move_by(&mut self, dx: i32, dy: i32) -> Result<()> {
+        self.device.emit(&[
+            InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_X.0, dx),
+            InputEvent::new(EventType::RELATIVE.0, RelativeAxisCode::REL_Y.0, dy),
+        ])?;
+        Ok(())
+    }
+
+    fn scroll(&mut self, vertical: i32, horizontal: i32) -> Result<()> {
+        self.device.emit(&[
+            InputEvent::new(
+                EventType::RELATIVE.0,
+                RelativeAxisCode::REL_WHEEL.0,
+                vertical,
+            ),
+            InputEvent::new(
+                EventType::RELATIVE.0,
+                RelativeAxisCode::REL_HWHEEL.0,
+                horizontal,
+            ),
+        ])?;
+        Ok(())
+    }
+
+    fn key(&mut self, code: u16, pressed: bool) -> Result<()> {
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY.0, code, pressed as i32)])?;
+        Ok(())
+    }
 }
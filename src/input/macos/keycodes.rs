@@ -0,0 +1,90 @@
+use objc2_core_graphics::CGKeyCode;
+
+/// Converts a Linux input event code to a macOS virtual key code for an ANSI layout
+pub fn from_linux(code: u16) -> Option<CGKeyCode> {
+    Some(match code {
+        1 => 0x35,   // Escape
+        2 => 0x12,   // 1
+        3 => 0x13,   // 2
+        4 => 0x14,   // 3
+        5 => 0x15,   // 4
+        6 => 0x17,   // 5
+        7 => 0x16,   // 6
+        8 => 0x1A,   // 7
+        9 => 0x1C,   // 8
+        10 => 0x19,  // 9
+        11 => 0x1D,  // 0
+        12 => 0x1B,  // Minus
+        13 => 0x18,  // Equal
+        14 => 0x33,  // Backspace
+        15 => 0x30,  // Tab
+        16 => 0x0C,  // Q
+        17 => 0x0D,  // W
+        18 => 0x0E,  // E
+        19 => 0x0F,  // R
+        20 => 0x11,  // T
+        21 => 0x10,  // Y
+        22 => 0x20,  // U
+        23 => 0x22,  // I
+        24 => 0x1F,  // O
+        25 => 0x23,  // P
+        26 => 0x21,  // Left bracket
+        27 => 0x1E,  // Right bracket
+        28 => 0x24,  // Enter
+        29 => 0x3B,  // Left ctrl
+        30 => 0x00,  // A
+        31 => 0x01,  // S
+        32 => 0x02,  // D
+        33 => 0x03,  // F
+        34 => 0x05,  // G
+        35 => 0x04,  // H
+        36 => 0x26,  // J
+        37 => 0x28,  // K
+        38 => 0x25,  // L
+        39 => 0x29,  // Semicolon
+        40 => 0x27,  // Apostrophe
+        41 => 0x32,  // Grave
+        42 => 0x38,  // Left shift
+        43 => 0x2A,  // Backslash
+        44 => 0x06,  // Z
+        45 => 0x07,  // X
+        46 => 0x08,  // C
+        47 => 0x09,  // V
+        48 => 0x0B,  // B
+        49 => 0x2D,  // N
+        50 => 0x2E,  // M
+        51 => 0x2B,  // Comma
+        52 => 0x2F,  // Dot
+        53 => 0x2C,  // Slash
+        54 => 0x3C,  // Right shift
+        56 => 0x3A,  // Left alt
+        57 => 0x31,  // Space
+        58 => 0x39,  // Caps lock
+        59 => 0x7A,  // F1
+        60 => 0x78,  // F2
+        61 => 0x63,  // F3
+        62 => 0x76,  // F4
+        63 => 0x60,  // F5
+        64 => 0x61,  // F6
+        65 => 0x62,  // F7
+        66 => 0x64,  // F8
+        67 => 0x65,  // F9
+        68 => 0x6D,  // F10
+        87 => 0x67,  // F11
+        88 => 0x6F,  // F12
+        97 => 0x3E,  // Right ctrl
+        100 => 0x3D, // Right alt
+        102 => 0x73, // Home
+        103 => 0x7E, // Up
+        104 => 0x74, // Page up
+        105 => 0x7B, // Left
+        106 => 0x7C, // Right
+        107 => 0x77, // End
+        108 => 0x7D, // Down
+        109 => 0x79, // Page down
+        111 => 0x75, // Delete
+        125 => 0x37, // Left meta
+        126 => 0x36, // Right meta
+        _ => return None,
+    })
+}
@@ -1,13 +1,14 @@
 mod accessibility;
+mod keycodes;
 
 use super::{InputBackend, MouseButton};
-use anyhow::Result;
+use anyhow::{Result, bail};
 use objc2::rc::autoreleasepool;
 use objc2_app_kit::NSEvent;
 use objc2_core_foundation::{CFRetained, CGPoint};
 use objc2_core_graphics::{
     CGDisplayPixelsHigh, CGEvent, CGEventSource, CGEventSourceStateID, CGEventTapLocation,
-    CGEventType, CGMainDisplayID, CGMouseButton,
+    CGEventType, CGMainDisplayID, CGMouseButton, CGScrollEventUnit,
 };
 
 pub struct PlatformInput {
@@ -54,6 +55,18 @@ impl PlatformInput {
             y: self.display_height - pos.y,
         }
     }
+
+    fn post_mouse_event(&self, button: MouseButton, event_type: CGEventType) {
+        let pos = self.get_mouse_position();
+        let cg_button = Self::mouse_button_to_cg(button);
+        let source = self.source.as_deref();
+
+        autoreleasepool(|_| {
+            if let Some(event) = CGEvent::new_mouse_event(source, event_type, pos, cg_button) {
+                CGEvent::post(CGEventTapLocation::HIDEventTap, Some(&*event));
+            }
+        });
+    }
 }
 
 impl InputBackend for PlatformInput {
@@ -74,4 +87,69 @@ impl InputBackend for PlatformInput {
 
         Ok(())
     }
+
+    fn press(&mut self, button: MouseButton) -> Result<()> {
+        let (down_type, _) = Self::get_event_types(button);
+        self.post_mouse_event(button, down_type);
+        Ok(())
+    }
+
+    fn release(&mut self, button: MouseButton) -> Result<()> {
+        let (_, up_type) = Self::get_event_types(button);
+        self.post_mouse_event(button, up_type);
+        Ok(())
+    }
+
+    fn move_by(&mut self, dx: i32, dy: i32) -> Result<()> {
+        let pos = self.get_mouse_position();
+        let target = CGPoint {
+            x: pos.x + dx as f64,
+            y: pos.y + dy as f64,
+        };
+        let source = self.source.as_deref();
+
+        autoreleasepool(|_| {
+            if let Some(event) = CGEvent::new_mouse_event(
+                source,
+                CGEventType::MouseMoved,
+                target,
+                CGMouseButton::Left,
+            ) {
+                CGEvent::post(CGEventTapLocation::HIDEventTap, Some(&*event));
+            }
+        });
+        Ok(())
+    }
+
+    fn scroll(&mut self, vertical: i32, horizontal: i32) -> Result<()> {
+        let source = self.source.as_deref();
+
+        autoreleasepool(|_| {
+            if let Some(event) = CGEvent::new_scroll_wheel_event2(
+                source,
+                CGScrollEventUnit::Line,
+                2,
+                vertical,
+                horizontal,
+                0,
+            ) {
+                CGEvent::post(CGEventTapLocation::HIDEventTap, Some(&*event));
+            }
+        });
+        Ok(())
+    }
+
+    fn key(&mut self, code: u16, pressed: bool) -> Result<()> {
+        let Some(keycode) = keycodes::from_linux(code) else {
+            bail!("Key code {code} can't be replayed on macOS");
+        };
+        let source = self.source.as_deref();
+
+        autoreleasepool(|_| {
+            if let Some(event) = CGEvent::new_keyboard_event(source, keycode, pressed) {
+                CGEvent::post(CGEventTapLocation::HIDEventTap, Some(&*event));
+            }
+        });
+        Ok(())
+    }
 }
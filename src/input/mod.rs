@@ -2,6 +2,9 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::{fmt, time::Duration};
 
+mod capture;
+pub use capture::{CaptureDevices, CaptureKind, CapturedEvent, InputCapture};
+
 cfg_if::cfg_if! {
     if #[cfg(target_os = "linux")] {
         mod linux;
@@ -17,13 +20,18 @@ cfg_if::cfg_if! {
     }
 }
 
+/// Time between the two clicks of a double click
+pub const DOUBLE_CLICK_GAP: Duration = Duration::from_millis(50);
+/// Time for the pointer to settle after moving before clicking
+pub const MOVE_SETTLE: Duration = Duration::from_millis(10);
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct Coordinates {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum MouseButton {
     Left,
     Right,
@@ -67,22 +75,40 @@ impl ClickAction {
     }
 }
 
+/// Sends input on a platform, or anywhere else clicks should go
 pub trait InputBackend: Send {
     fn click(&mut self, button: MouseButton) -> Result<()>;
+    fn press(&mut self, button: MouseButton) -> Result<()>;
+    fn release(&mut self, button: MouseButton) -> Result<()>;
     fn move_to(&mut self, coords: Coordinates) -> Result<()>;
+    /// Moves the pointer relative to where it is now
+    fn move_by(&mut self, dx: i32, dy: i32) -> Result<()>;
+    /// Scrolls by whole wheel notches, positive being up and right
+    fn scroll(&mut self, vertical: i32, horizontal: i32) -> Result<()>;
+    /// Presses or releases the key with the given Linux input event code
+    fn key(&mut self, code: u16, pressed: bool) -> Result<()>;
 }
 
+/// Turns clicks and recorded input into calls on an [`InputBackend`]
 pub struct InputHandler {
-    backend: PlatformInput,
+    backend: Box<dyn InputBackend>,
 }
 
 impl InputHandler {
+    /// Sends input with the platform's backend
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            backend: PlatformInput::new()?,
-        })
+        Ok(Self::with_backend(PlatformInput::new()?))
+    }
+
+    /// Sends input with `backend`, for platforms and devices Click doesn't
+    /// cover or for testing
+    pub fn with_backend(backend: impl InputBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
     }
 
+    /// Clicks `button`
     pub fn click(&mut self, button: MouseButton, click_action: ClickAction) -> Result<()> {
         match click_action {
             ClickAction::Single => {
@@ -90,7 +116,7 @@ impl InputHandler {
             }
             ClickAction::Double => {
                 self.backend.click(button)?;
-                std::thread::sleep(Duration::from_millis(50));
+                std::thread::sleep(DOUBLE_CLICK_GAP);
                 self.backend.click(button)?;
             }
         }
@@ -104,8 +130,33 @@ impl InputHandler {
         click_action: ClickAction,
     ) -> Result<()> {
         self.backend.move_to(coords)?;
-        std::thread::sleep(Duration::from_millis(10));
+        std::thread::sleep(MOVE_SETTLE);
         self.click(button, click_action)?;
         Ok(())
     }
+
+    pub fn move_to(&mut self, coords: Coordinates) -> Result<()> {
+        self.backend.move_to(coords)
+    }
+
+    /// Replays a single captured input event
+    pub fn send(&mut self, kind: CaptureKind) -> Result<()> {
+        match kind {
+            CaptureKind::Button {
+                button,
+                pressed: true,
+            } => self.backend.press(button),
+            CaptureKind::Button {
+                button,
+                pressed: false,
+            } => self.backend.release(button),
+            CaptureKind::Move { dx, dy } => self.backend.move_by(dx, dy),
+            CaptureKind::MoveTo { x, y } => self.backend.move_to(Coordinates { x, y }),
+            CaptureKind::Wheel {
+                vertical,
+                horizontal,
+            } => self.backend.scroll(vertical, horizontal),
+            CaptureKind::Key { code, pressed } => self.backend.key(code, pressed),
+        }
+    }
 }
@@ -1,11 +1,16 @@
 use super::{InputBackend, MouseButton};
 use anyhow::Result;
 use windows::Win32::UI::Input::KeyboardAndMouse::{
-    INPUT, INPUT_0, INPUT_MOUSE, MOUSE_EVENT_FLAGS, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
-    MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP,
-    MOUSEINPUT, SendInput,
+    INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBD_EVENT_FLAGS, KEYBDINPUT,
+    KEYEVENTF_EXTENDEDKEY, KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, MOUSE_EVENT_FLAGS,
+    MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN,
+    MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP,
+    MOUSEEVENTF_WHEEL, MOUSEINPUT, SendInput, VIRTUAL_KEY,
 };
 
+/// Distance of one wheel notch
+const WHEEL_DELTA: i32 = 120;
+
 pub struct PlatformInput;
 
 impl PlatformInput {
@@ -19,13 +24,17 @@ impl PlatformInput {
     }
 
     fn create_mouse_input(flag: MOUSE_EVENT_FLAGS) -> INPUT {
+        Self::create_mouse_input_with(flag, 0, 0, 0)
+    }
+
+    fn create_mouse_input_with(flag: MOUSE_EVENT_FLAGS, dx: i32, dy: i32, data: i32) -> INPUT {
         INPUT {
             r#type: INPUT_MOUSE,
             Anonymous: INPUT_0 {
                 mi: MOUSEINPUT {
-                    dx: 0,
-                    dy: 0,
-                    mouseData: 0,
+                    dx,
+                    dy,
+                    mouseData: data as u32,
                     dwFlags: flag,
                     time: 0,
                     dwExtraInfo: 0,
@@ -34,6 +43,38 @@ impl PlatformInput {
         }
     }
 
+    /// Converts a Linux input event code to a set 1 scan code and whether it is
+    /// an extended key. Codes up to F12 already are scan codes.
+    fn code_to_scan(code: u16) -> Option<(u16, bool)> {
+        match code {
+            1..=88 => Some((code, false)),
+            96 => Some((0x1C, true)),  // Keypad enter
+            97 => Some((0x1D, true)),  // Right ctrl
+            98 => Some((0x35, true)),  // Keypad slash
+            100 => Some((0x38, true)), // Right alt
+            102 => Some((0x47, true)), // Home
+            103 => Some((0x48, true)), // Up
+            104 => Some((0x49, true)), // Page up
+            105 => Some((0x4B, true)), // Left
+            106 => Some((0x4D, true)), // Right
+            107 => Some((0x4F, true)), // End
+            108 => Some((0x50, true)), // Down
+            109 => Some((0x51, true)), // Page down
+            110 => Some((0x52, true)), // Insert
+            111 => Some((0x53, true)), // Delete
+            125 => Some((0x5B, true)), // Left meta
+            126 => Some((0x5C, true)), // Right meta
+            127 => Some((0x5D, true)), // Menu
+            _ => None,
+        }
+    }
+
+    fn send(inputs: &[INPUT]) {
+        unsafe {
+            SendInput(inputs, std::mem::size_of::<INPUT>() as i32);
+        }
+    }
+
     fn button_to_flags(button: MouseButton) -> (MOUSE_EVENT_FLAGS, MOUSE_EVENT_FLAGS) {
         match button {
             MouseButton::Left => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP),
@@ -58,4 +99,80 @@ impl InputBackend for PlatformInput {
 
         Ok(())
     }
+
+    fn press(&mut self, button: MouseButton) -> Result<()> {
+        let (flag_down, _) = Self::button_to_flags(button);
+        let inputs = [Self::create_mouse_input(flag_down)];
+
+        unsafe {
+            SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+        }
+
+        Ok(())
+    }
+
+    fn release(&mut self, button: MouseButton) -> Result<()> {
+        let (_, flag_up) = Self::button_to_flags(button);
+        let inputs = [Self::create_mouse_input(flag_up)];
+
+        unsafe {
+            SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+        }
+
+        Ok(())
+    }
+
+    fn move_by(&mut self, dx: i32, dy: i32) -> Result<()> {
+        Self::send(&[Self::create_mouse_input_with(MOUSEEVENTF_MOVE, dx, dy, 0)]);
+        Ok(())
+    }
+
+    fn scroll(&mut self, vertical: i32, horizontal: i32) -> Result<()> {
+        if vertical != 0 {
+            Self::send(&[Self::create_mouse_input_with(
+                MOUSEEVENTF_WHEEL,
+                0,
+                0,
+                vertical * WHEEL_DELTA,
+            )]);
+        }
+        if horizontal != 0 {
+            Self::send(&[Self::create_mouse_input_with(
+                MOUSEEVENTF_HWHEEL,
+                0,
+                0,
+                horizontal * WHEEL_DELTA,
+            )]);
+        }
+        Ok(())
+    }
+
+    fn key(&mut self, code: u16, pressed: bool) -> Result<()> {
+        let Some((scan, extended)) = Self::code_to_scan(code) else {
+            anyhow::bail!("Key code {code} can't be replayed on Windows");
+        };
+
+        let none = KEYBD_EVENT_FLAGS(0);
+        let flags = KEYEVENTF_SCANCODE
+            | if extended {
+                KEYEVENTF_EXTENDEDKEY
+            } else {
+                none
+            }
+            | if pressed { none } else { KEYEVENTF_KEYUP };
+
+        Self::send(&[INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(0),
+                    wScan: scan,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                },
+            },
+        }]);
+        Ok(())
+    }
 }
@@ -1,7 +1,9 @@
-use std::time::Duration;
+use std::{fmt, time::Duration};
 
-use rand::Rng;
-use rand_distr::{Distribution, Gamma, Normal};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rand_distr::{Distribution, Gamma, Normal, StandardNormal};
+
+use crate::learned::LearnedProfile;
 
 #[derive(PartialEq, serde::Deserialize, serde::Serialize, Clone, Copy)]
 pub enum IntervalMode {
@@ -9,13 +11,14 @@ pub enum IntervalMode {
     Cps,
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Copy)]
+#[derive(PartialEq, serde::Deserialize, serde::Serialize, Clone, Copy)]
 #[serde(default)]
 pub struct TimeInterval {
     pub hours: u8,
     pub minutes: u8,
     pub seconds: u8,
     pub milliseconds: u16,
+    pub microseconds: u16,
 }
 
 impl Default for TimeInterval {
@@ -25,49 +28,208 @@ impl Default for TimeInterval {
             minutes: 0,
             seconds: 0,
             milliseconds: 200,
+            microseconds: 0,
         }
     }
 }
 
 impl TimeInterval {
     pub fn to_duration(self) -> Duration {
-        let total_ms = (self.hours as u64) * 3600 * 1000
-            + (self.minutes as u64) * 60 * 1000
-            + (self.seconds as u64) * 1000
-            + (self.milliseconds as u64);
-        Duration::from_millis(total_ms.max(1))
+        let total_us = (self.hours as u64) * 3600 * 1_000_000
+            + (self.minutes as u64) * 60 * 1_000_000
+            + (self.seconds as u64) * 1_000_000
+            + (self.milliseconds as u64) * 1000
+            + (self.microseconds as u64);
+        Duration::from_micros(total_us.max(1))
+    }
+}
+
+/// K clicks at a fixed interval, followed by a rest before the next burst
+#[derive(PartialEq, serde::Deserialize, serde::Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct BurstPattern {
+    pub clicks: u32,
+    pub interval: TimeInterval,
+    pub rest: TimeInterval,
+}
+
+impl Default for BurstPattern {
+    fn default() -> Self {
+        Self {
+            clicks: 5,
+            interval: TimeInterval {
+                milliseconds: 50,
+                ..TimeInterval::default()
+            },
+            rest: TimeInterval {
+                seconds: 1,
+                milliseconds: 0,
+                ..TimeInterval::default()
+            },
+        }
+    }
+}
+
+impl BurstPattern {
+    /// Returns whether click number `click_index`, counting from zero, starts a
+    /// new burst and so comes after a rest
+    pub fn rests_before(&self, click_index: u32) -> bool {
+        click_index > 0 && click_index.is_multiple_of(self.clicks.max(1))
+    }
+
+    /// Returns the number of clicks that make up `bursts` full bursts
+    pub fn clicks_in(&self, bursts: u32) -> u32 {
+        self.clicks.max(1).saturating_mul(bursts)
+    }
+}
+
+#[derive(PartialEq, serde::Deserialize, serde::Serialize, Clone, Copy)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+}
+
+impl fmt::Display for Easing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Easing::Linear => write!(f, "Linear"),
+            Easing::EaseIn => write!(f, "Ease in"),
+            Easing::EaseOut => write!(f, "Ease out"),
+        }
+    }
+}
+
+impl Easing {
+    pub fn all() -> [Easing; 3] {
+        [Easing::Linear, Easing::EaseIn, Easing::EaseOut]
+    }
+
+    /// Maps linear progress in `0.0..=1.0` onto the curve
+    pub fn apply(self, progress: f64) -> f64 {
+        let t = progress.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+/// Gradually changes the click rate between `start_cps` and the configured target
+#[derive(PartialEq, serde::Deserialize, serde::Serialize, Clone, Copy)]
+#[serde(default)]
+pub struct Ramp {
+    pub start_cps: f64,
+    pub duration: TimeInterval,
+    pub easing: Easing,
+    pub use_ramp_down: bool,
+    /// Number of clicks before the limit over which the rate falls back to `start_cps`
+    pub ramp_down_clicks: u32,
+}
+
+impl Default for Ramp {
+    fn default() -> Self {
+        Self {
+            start_cps: 2.0,
+            duration: TimeInterval {
+                seconds: 5,
+                milliseconds: 0,
+                ..TimeInterval::default()
+            },
+            easing: Easing::Linear,
+            use_ramp_down: false,
+            ramp_down_clicks: 20,
+        }
     }
 }
 
-pub struct Jitter {
+impl Ramp {
+    /// Returns the interval to use instead of `target` after running for `elapsed`,
+    /// with `remaining` clicks left before the run's click limit
+    pub fn apply(&self, target: Duration, elapsed: Duration, remaining: Option<u32>) -> Duration {
+        let target_cps = 1.0 / target.as_secs_f64();
+        let start_cps = self.start_cps.max(f64::MIN_POSITIVE);
+
+        let ramp_up = elapsed.as_secs_f64() / self.duration.to_duration().as_secs_f64();
+        let mut progress = self.easing.apply(ramp_up);
+
+        if self.use_ramp_down
+            && let Some(remaining) = remaining
+            && remaining < self.ramp_down_clicks
+        {
+            let ramp_down = remaining as f64 / self.ramp_down_clicks as f64;
+            progress = progress.min(self.easing.apply(ramp_down));
+        }
+
+        let cps = start_cps + (target_cps - start_cps) * progress;
+        Duration::from_secs_f64(1.0 / cps)
+    }
+}
+
+pub struct Jitter<R = StdRng> {
+    rng: R,
     /// Smoothed offset that creates momentum between clicks
     last_offset: f64,
     click_count: u32,
+    /// Replaces the built-in model when set
+    profile: Option<LearnedProfile>,
+    /// Gaussian state carrying the learned autocorrelation between intervals
+    learned_state: f64,
 }
 
 impl Jitter {
-    pub fn new() -> Self {
+    /// Creates a generator seeded from `seed`, so the same seed always
+    /// produces the same delay sequence
+    pub fn from_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> Jitter<R> {
+    pub fn with_rng(rng: R) -> Self {
         Self {
+            rng,
             last_offset: 0.0,
             click_count: 0,
+            profile: None,
+            learned_state: 0.0,
         }
     }
 
+    /// Samples delays and hold times from `profile` instead of the built-in model,
+    /// unless the profile isn't usable
+    pub fn with_profile(mut self, profile: Option<LearnedProfile>) -> Self {
+        self.profile = profile.filter(LearnedProfile::is_usable);
+        self
+    }
+
     /// Returns the next click delay with humanized timing variations
     ///
     /// `jitter` parameter controls the standard deviation of timing variations in milliseconds
     pub fn next(&mut self, base: Duration, jitter: u16) -> Duration {
-        let base_ms = base.as_millis() as f64;
-        let mut rng = rand::rng();
+        if let Some(profile) = &self.profile {
+            // AR(1) process in normal space, mapped through the recorded distribution
+            let rho = profile.autocorrelation;
+            let noise: f64 = StandardNormal.sample(&mut self.rng);
+            self.learned_state = rho * self.learned_state + (1.0 - rho * rho).sqrt() * noise;
+            let ratio = profile.ratio_at(normal_cdf(self.learned_state));
+
+            self.click_count += 1;
+            return base.mul_f64(ratio).max(Duration::from_micros(1));
+        }
+
+        let base_ms = base.as_secs_f64() * 1000.0;
 
         // Normal distribution for continuous small variations
         let normal = Normal::new(0.0, jitter as f64 / 3.0).unwrap();
-        let quick_jitter = normal.sample(&mut rng);
+        let quick_jitter = normal.sample(&mut self.rng);
 
         // 3% chance of hesitation spike using gamma distribution
-        let hesitation = if rng.random::<f64>() < 0.03 {
-            let gamma = Gamma::new(2.0, jitter as f64 * 0.5).unwrap();
-            gamma.sample(&mut rng)
+        let hesitation = if self.rng.random::<f64>() < 0.03
+            && let Ok(gamma) = Gamma::new(2.0, jitter as f64 * 0.5)
+        {
+            gamma.sample(&mut self.rng)
         } else {
             0.0
         };
@@ -75,8 +237,179 @@ impl Jitter {
         // Exponential moving average for smooth rhythm changes
         self.last_offset = (self.last_offset * 0.85) + (quick_jitter * 0.15);
 
-        let final_ms = (base_ms + self.last_offset + hesitation).max(1.0);
+        let final_ms = (base_ms + self.last_offset + hesitation).max(0.001);
         self.click_count += 1;
-        Duration::from_millis(final_ms.round() as u64)
+        Duration::from_secs_f64(final_ms / 1000.0)
+    }
+
+    /// Returns how long to hold the button down, if the learned profile recorded it
+    pub fn next_hold(&mut self) -> Option<Duration> {
+        let profile = self.profile.as_ref()?;
+        let mean = profile.hold_mean_ms?;
+        let hold_ms = Normal::new(mean, profile.hold_std_ms)
+            .map(|normal| normal.sample(&mut self.rng))
+            .unwrap_or(mean);
+        Some(Duration::from_secs_f64(hold_ms.max(1.0) / 1000.0))
+    }
+}
+
+/// Standard normal CDF via the Abramowitz and Stegun erf approximation
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Duration = Duration::from_millis(100);
+
+    fn delays_ms(jitter_gen: &mut Jitter, jitter: u16, count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|_| jitter_gen.next(BASE, jitter).as_secs_f64() * 1000.0)
+            .collect()
+    }
+
+    fn mean_and_spread(values: &[f64]) -> (f64, f64) {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+        (mean, variance.sqrt())
+    }
+
+    fn burst(clicks: u32) -> BurstPattern {
+        BurstPattern {
+            clicks,
+            ..BurstPattern::default()
+        }
+    }
+
+    #[test]
+    fn bursts_rest_before_every_kth_click() {
+        let pattern = burst(3);
+        let rests: Vec<u32> = (0..10).filter(|i| pattern.rests_before(*i)).collect();
+
+        assert_eq!(rests, [3, 6, 9]);
+        assert_eq!(pattern.clicks_in(0), 0);
+        assert_eq!(pattern.clicks_in(4), 12);
+        assert_eq!(pattern.clicks_in(u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn empty_bursts_count_as_single_clicks() {
+        let pattern = burst(0);
+
+        assert!(!pattern.rests_before(0));
+        assert!((1..5).all(|i| pattern.rests_before(i)));
+        assert_eq!(pattern.clicks_in(4), 4);
+    }
+
+    fn ramp(easing: Easing) -> Ramp {
+        Ramp {
+            start_cps: 2.0,
+            duration: TimeInterval {
+                seconds: 4,
+                milliseconds: 0,
+                ..TimeInterval::default()
+            },
+            easing,
+            use_ramp_down: true,
+            ramp_down_clicks: 10,
+        }
+    }
+
+    fn cps(interval: Duration) -> f64 {
+        1.0 / interval.as_secs_f64()
+    }
+
+    #[test]
+    fn easing_curves_meet_at_the_ends() {
+        for easing in Easing::all() {
+            assert_eq!(easing.apply(0.0), 0.0, "{easing}");
+            assert_eq!(easing.apply(1.0), 1.0, "{easing}");
+            assert_eq!(easing.apply(-1.0), 0.0, "{easing}");
+            assert_eq!(easing.apply(2.0), 1.0, "{easing}");
+        }
+        assert_eq!(Easing::Linear.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+    }
+
+    #[test]
+    fn ramps_from_the_start_rate_to_the_target() {
+        let target = Duration::from_millis(100);
+        let ramp = ramp(Easing::Linear);
+        let at = |seconds| cps(ramp.apply(target, Duration::from_secs(seconds), None));
+
+        assert!((at(0) - 2.0).abs() < 1e-6);
+        assert!((at(2) - 6.0).abs() < 1e-6);
+        assert!((at(4) - 10.0).abs() < 1e-6);
+        assert!((at(60) - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ramps_down_over_the_last_clicks() {
+        let target = Duration::from_millis(100);
+        let ramp = ramp(Easing::Linear);
+        let at = |remaining| cps(ramp.apply(target, Duration::from_secs(60), Some(remaining)));
+
+        assert!((at(10) - 10.0).abs() < 1e-6);
+        assert!((at(5) - 6.0).abs() < 1e-6);
+        assert!((at(0) - 2.0).abs() < 1e-6);
+
+        let no_ramp_down = Ramp {
+            use_ramp_down: false,
+            ..ramp
+        };
+        let interval = no_ramp_down.apply(target, Duration::from_secs(60), Some(0));
+        assert!((cps(interval) - 10.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_delays() {
+        let first = delays_ms(&mut Jitter::from_seed(42), 30, 500);
+        let second = delays_ms(&mut Jitter::from_seed(42), 30, 500);
+        let other = delays_ms(&mut Jitter::from_seed(43), 30, 500);
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn small_variations_stay_close_to_the_base() {
+        let delays = delays_ms(&mut Jitter::from_seed(1), 30, 20_000);
+        let (mean, spread) = mean_and_spread(&delays);
+
+        // Hesitations only ever add time, about 3% of 30ms on average
+        assert!((100.0..102.5).contains(&mean), "mean {mean}");
+        assert!((3.0..12.0).contains(&spread), "spread {spread}");
+        assert!(delays.iter().all(|ms| *ms > 80.0));
+    }
+
+    #[test]
+    fn hesitations_are_rare_and_long() {
+        let delays = delays_ms(&mut Jitter::from_seed(2), 30, 20_000);
+        // Plain variation stays within a few milliseconds, so this only counts spikes
+        let spikes = delays.iter().filter(|ms| **ms > 115.0).count();
+        let share = spikes as f64 / delays.len() as f64;
+
+        assert!((0.01..0.05).contains(&share), "share {share}");
+    }
+
+    #[test]
+    fn more_jitter_spreads_delays_further() {
+        let (_, narrow) = mean_and_spread(&delays_ms(&mut Jitter::from_seed(3), 10, 10_000));
+        let (_, wide) = mean_and_spread(&delays_ms(&mut Jitter::from_seed(3), 60, 10_000));
+
+        assert!(wide > narrow * 3.0, "{narrow} vs {wide}");
     }
 }
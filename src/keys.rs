@@ -0,0 +1,97 @@
+//! Names for Linux input event key codes, which is how keys are stored in
+//! macros and sequences on every platform
+
+use eframe::egui::Key;
+
+/// Keys offered in pickers, by Linux input event code
+pub const KEYS: &[(u16, &str)] = &[
+    (30, "A"),
+    (48, "B"),
+    (46, "C"),
+    (32, "D"),
+    (18, "E"),
+    (33, "F"),
+    (34, "G"),
+    (35, "H"),
+    (23, "I"),
+    (36, "J"),
+    (37, "K"),
+    (38, "L"),
+    (50, "M"),
+    (49, "N"),
+    (24, "O"),
+    (25, "P"),
+    (16, "Q"),
+    (19, "R"),
+    (31, "S"),
+    (20, "T"),
+    (22, "U"),
+    (47, "V"),
+    (17, "W"),
+    (45, "X"),
+    (21, "Y"),
+    (44, "Z"),
+    (11, "0"),
+    (2, "1"),
+    (3, "2"),
+    (4, "3"),
+    (5, "4"),
+    (6, "5"),
+    (7, "6"),
+    (8, "7"),
+    (9, "8"),
+    (10, "9"),
+    (57, "Space"),
+    (28, "Enter"),
+    (15, "Tab"),
+    (1, "Escape"),
+    (14, "Backspace"),
+    (111, "Delete"),
+    (110, "Insert"),
+    (102, "Home"),
+    (107, "End"),
+    (104, "Page up"),
+    (109, "Page down"),
+    (103, "Up"),
+    (108, "Down"),
+    (105, "Left"),
+    (106, "Right"),
+    (42, "Shift"),
+    (29, "Ctrl"),
+    (56, "Alt"),
+    (125, "Super"),
+    (59, "F1"),
+    (60, "F2"),
+    (61, "F3"),
+    (62, "F4"),
+    (63, "F5"),
+    (64, "F6"),
+    (65, "F7"),
+    (66, "F8"),
+    (67, "F9"),
+    (68, "F10"),
+    (87, "F11"),
+    (88, "F12"),
+];
+
+/// Returns a readable name for `code`, falling back to the number itself
+pub fn name(code: u16) -> String {
+    KEYS.iter()
+        .find(|(key, _)| *key == code)
+        .map(|(_, name)| (*name).to_owned())
+        .unwrap_or_else(|| format!("Key {code}"))
+}
+
+/// Linux input event code of an egui key, so hotkeys can be spotted in
+/// captured input while another window has focus
+pub fn from_egui(key: Key) -> Option<u16> {
+    let name = match key {
+        Key::PageUp => "Page up",
+        Key::PageDown => "Page down",
+        other => other.name(),
+    };
+
+    KEYS.iter()
+        .find(|(_, key_name)| *key_name == name)
+        .map(|(code, _)| *code)
+}
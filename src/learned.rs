@@ -0,0 +1,351 @@
+use std::time::SystemTime;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::input::{CaptureDevices, CaptureKind, CapturedEvent, InputCapture, MouseButton};
+
+/// Number of evenly spaced quantiles kept from the recorded interval distribution
+const QUANTILES: usize = 33;
+/// Minimum number of inter-click intervals needed for a usable profile
+const MIN_INTERVALS: usize = 20;
+/// Gaps longer than this multiple of the median are pauses, not part of the rhythm
+const PAUSE_FACTOR: f64 = 4.0;
+/// Keeps the sampled sequence from locking onto a single value
+const MAX_AUTOCORRELATION: f64 = 0.9;
+
+/// Click timing fitted from a recording of someone clicking at their natural pace
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LearnedProfile {
+    /// Inter-click intervals divided by their mean, at evenly spaced quantiles
+    quantiles: Vec<f64>,
+    /// Mean inter-click interval of the recording in milliseconds
+    pub mean_interval_ms: f64,
+    /// Lag-1 autocorrelation of consecutive intervals
+    pub autocorrelation: f64,
+    /// Mean and standard deviation of how long the button was held, in milliseconds
+    pub hold_mean_ms: Option<f64>,
+    pub hold_std_ms: f64,
+    /// Number of intervals the profile was fitted from
+    pub samples: usize,
+}
+
+impl LearnedProfile {
+    /// Fits a profile from captured button events, using the most pressed button
+    pub fn fit(events: &[CapturedEvent]) -> Result<Self> {
+        let press_count = |button: MouseButton| {
+            events
+                .iter()
+                .filter(|event| {
+                    event.kind
+                        == CaptureKind::Button {
+                            button,
+                            pressed: true,
+                        }
+                })
+                .count()
+        };
+        let button = MouseButton::all()
+            .into_iter()
+            .max_by_key(|button| press_count(*button))
+            .unwrap_or(MouseButton::Left);
+
+        let mut presses = Vec::new();
+        let mut holds = Vec::new();
+        let mut pressed_at: Option<SystemTime> = None;
+        for event in events {
+            let CaptureKind::Button {
+                button: event_button,
+                pressed,
+            } = event.kind
+            else {
+                continue;
+            };
+            if event_button != button {
+                continue;
+            }
+
+            if pressed {
+                presses.push(event.time);
+                pressed_at = Some(event.time);
+            } else if let Some(start) = pressed_at.take()
+                && let Ok(held) = event.time.duration_since(start)
+            {
+                holds.push(held.as_secs_f64() * 1000.0);
+            }
+        }
+
+        let raw: Vec<f64> = presses
+            .windows(2)
+            .filter_map(|pair| pair[1].duration_since(pair[0]).ok())
+            .map(|gap| gap.as_secs_f64() * 1000.0)
+            .filter(|ms| *ms > 0.0)
+            .collect();
+
+        let median = {
+            let mut sorted = raw.clone();
+            sorted.sort_by(f64::total_cmp);
+            sorted.get(sorted.len() / 2).copied().unwrap_or(0.0)
+        };
+        let intervals: Vec<f64> = raw
+            .into_iter()
+            .filter(|ms| *ms <= median * PAUSE_FACTOR)
+            .collect();
+
+        if intervals.len() < MIN_INTERVALS {
+            bail!(
+                "Need at least {} clicks to learn from, recorded {}",
+                MIN_INTERVALS + 1,
+                intervals.len() + 1
+            );
+        }
+
+        let mean_interval = mean(&intervals);
+        let mut normalized: Vec<f64> = intervals.iter().map(|ms| ms / mean_interval).collect();
+        let autocorrelation = lag1_autocorrelation(&normalized);
+
+        normalized.sort_by(f64::total_cmp);
+        let quantiles = (0..QUANTILES)
+            .filter_map(|i| interpolate(&normalized, i as f64 / (QUANTILES - 1) as f64))
+            .collect();
+
+        let (hold_mean_ms, hold_std_ms) = if holds.is_empty() {
+            (None, 0.0)
+        } else {
+            let hold_mean = mean(&holds);
+            (Some(hold_mean), std_dev(&holds, hold_mean))
+        };
+
+        Ok(Self {
+            quantiles,
+            mean_interval_ms: mean_interval,
+            autocorrelation: autocorrelation.clamp(-MAX_AUTOCORRELATION, MAX_AUTOCORRELATION),
+            hold_mean_ms,
+            hold_std_ms,
+            samples: intervals.len(),
+        })
+    }
+
+    /// Returns the interval, relative to the mean, found at cumulative probability `p`
+    pub fn ratio_at(&self, p: f64) -> f64 {
+        interpolate(&self.quantiles, p).unwrap_or(1.0)
+    }
+
+    /// Whether the profile can be sampled from. Saved profiles can be edited by
+    /// hand, so a loaded one may have an empty or unsorted quantile table
+    pub fn is_usable(&self) -> bool {
+        self.quantiles.len() >= 2
+            && self.quantiles.iter().all(|q| q.is_finite() && *q >= 0.0)
+            && self.quantiles.is_sorted()
+            && self.mean_interval_ms.is_finite()
+            && self.mean_interval_ms > 0.0
+            && self.autocorrelation.abs() <= MAX_AUTOCORRELATION
+            && self.hold_mean_ms.is_none_or(f64::is_finite)
+            && self.hold_std_ms.is_finite()
+            && self.hold_std_ms >= 0.0
+    }
+}
+
+/// Reads a saved profile, dropping it if it isn't usable so clicking falls
+/// back to plain jitter
+pub(crate) fn deserialize_usable<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<LearnedProfile>, D::Error> {
+    let profile = Option::<LearnedProfile>::deserialize(deserializer)?;
+    Ok(profile.filter(LearnedProfile::is_usable))
+}
+
+/// Collects physical clicks for [`LearnedProfile::fit`]
+pub struct ProfileRecorder {
+    capture: InputCapture,
+    events: Vec<CapturedEvent>,
+}
+
+impl ProfileRecorder {
+    pub fn start() -> Result<Self> {
+        Ok(Self {
+            capture: InputCapture::start(CaptureDevices::Mice)?,
+            events: Vec::new(),
+        })
+    }
+
+    /// Pulls in newly captured events and returns the number of presses so far
+    pub fn poll(&mut self) -> usize {
+        self.events.extend(self.capture.drain());
+        self.events
+            .iter()
+            .filter(|event| matches!(event.kind, CaptureKind::Button { pressed: true, .. }))
+            .count()
+    }
+
+    pub fn finish(mut self) -> Result<LearnedProfile> {
+        self.poll();
+        LearnedProfile::fit(&self.events)
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn std_dev(values: &[f64], mean: f64) -> f64 {
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    variance.sqrt()
+}
+
+fn lag1_autocorrelation(values: &[f64]) -> f64 {
+    let mean = mean(values);
+    let variance: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    if variance <= f64::EPSILON {
+        return 0.0;
+    }
+
+    let covariance: f64 = values
+        .windows(2)
+        .map(|pair| (pair[0] - mean) * (pair[1] - mean))
+        .sum();
+    covariance / variance
+}
+
+/// Linearly interpolates a sorted slice at position `p` in `0.0..=1.0`, or
+/// returns `None` when the slice is empty
+fn interpolate(sorted: &[f64], p: f64) -> Option<f64> {
+    let last = sorted.len().checked_sub(1)?;
+    let position = p.clamp(0.0, 1.0) * last as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f64;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::interval::Jitter;
+
+    /// Left clicks held for 30ms, alternating 80ms and 120ms apart, with one
+    /// long pause in the middle and a stray right click
+    fn recording(clicks: usize) -> Vec<CapturedEvent> {
+        let start = SystemTime::UNIX_EPOCH;
+        let mut events = Vec::new();
+        let mut at = Duration::ZERO;
+        for i in 0..clicks {
+            let button = |pressed| CaptureKind::Button {
+                button: MouseButton::Left,
+                pressed,
+            };
+            events.push(CapturedEvent {
+                time: start + at,
+                kind: button(true),
+            });
+            events.push(CapturedEvent {
+                time: start + at + Duration::from_millis(30),
+                kind: button(false),
+            });
+            at += Duration::from_millis(if i % 2 == 0 { 80 } else { 120 });
+            if i == clicks / 2 {
+                at += Duration::from_secs(5);
+                events.push(CapturedEvent {
+                    time: start + at,
+                    kind: CaptureKind::Button {
+                        button: MouseButton::Right,
+                        pressed: true,
+                    },
+                });
+            }
+        }
+        events
+    }
+
+    fn profile_with_quantiles(quantiles: Vec<f64>) -> LearnedProfile {
+        LearnedProfile {
+            quantiles,
+            mean_interval_ms: 100.0,
+            autocorrelation: 0.0,
+            hold_mean_ms: None,
+            hold_std_ms: 0.0,
+            samples: MIN_INTERVALS,
+        }
+    }
+
+    #[test]
+    fn fit_learns_the_rhythm_and_hold() {
+        let profile = LearnedProfile::fit(&recording(41)).unwrap();
+
+        // The pause is left out of the 40 intervals
+        assert_eq!(profile.samples, 39);
+        assert!((profile.mean_interval_ms - 100.0).abs() < 2.0);
+        assert!(profile.is_usable());
+        assert!((profile.ratio_at(0.0) - 0.8).abs() < 0.02);
+        assert!((profile.ratio_at(1.0) - 1.2).abs() < 0.02);
+        assert_eq!(profile.autocorrelation, -MAX_AUTOCORRELATION);
+        assert!((profile.hold_mean_ms.unwrap() - 30.0).abs() < 0.01);
+        assert!(profile.hold_std_ms < 0.01);
+    }
+
+    #[test]
+    fn fit_needs_enough_clicks() {
+        assert!(LearnedProfile::fit(&recording(5)).is_err());
+        assert!(LearnedProfile::fit(&[]).is_err());
+    }
+
+    #[test]
+    fn interpolate_between_quantiles() {
+        let sorted = [1.0, 2.0, 4.0];
+        assert_eq!(interpolate(&sorted, 0.0), Some(1.0));
+        assert_eq!(interpolate(&sorted, 0.25), Some(1.5));
+        assert_eq!(interpolate(&sorted, 0.75), Some(3.0));
+        assert_eq!(interpolate(&sorted, 1.0), Some(4.0));
+        assert_eq!(interpolate(&sorted, 2.0), Some(4.0));
+        assert_eq!(interpolate(&[0.5], 0.7), Some(0.5));
+        assert_eq!(interpolate(&[], 0.5), None);
+    }
+
+    #[test]
+    fn broken_quantile_tables_are_not_usable() {
+        for quantiles in [
+            vec![],
+            vec![1.0],
+            vec![1.2, 0.8, 1.0],
+            vec![0.8, f64::NAN, 1.2],
+            vec![-0.5, 1.0, 1.5],
+        ] {
+            let profile = profile_with_quantiles(quantiles.clone());
+            assert!(!profile.is_usable(), "{quantiles:?}");
+        }
+        assert_eq!(profile_with_quantiles(vec![]).ratio_at(0.5), 1.0);
+        assert!(profile_with_quantiles(vec![0.8, 1.0, 1.2]).is_usable());
+    }
+
+    #[test]
+    fn broken_saved_profiles_fall_back_to_plain_jitter() {
+        #[derive(Deserialize)]
+        struct Saved {
+            #[serde(deserialize_with = "deserialize_usable")]
+            profile: Option<LearnedProfile>,
+        }
+
+        let saved = |quantiles: &str| {
+            ron::from_str::<Saved>(&format!(
+                "(profile: Some((quantiles: {quantiles}, mean_interval_ms: 100.0, \
+                 autocorrelation: 0.0, hold_mean_ms: None, hold_std_ms: 0.0, samples: 20)))"
+            ))
+            .unwrap()
+            .profile
+        };
+        assert!(saved("[]").is_none());
+        assert!(saved("[1.2, 0.8]").is_none());
+        assert!(saved("[0.8, 1.2]").is_some());
+
+        let base = Duration::from_millis(100);
+        let mut jitter = Jitter::from_seed(1).with_profile(Some(profile_with_quantiles(vec![])));
+        for _ in 0..100 {
+            assert_eq!(jitter.next(base, 0), base);
+        }
+        assert_eq!(jitter.next_hold(), None);
+    }
+}
@@ -1,6 +1,25 @@
 mod app;
-mod input;
-mod interval;
+mod config;
+pub mod engine;
+mod evemu;
+mod files;
+mod focus;
+pub mod headless;
+pub mod input;
+pub mod interval;
+mod keys;
+pub mod learned;
+pub mod macros;
+mod migrate;
+mod profiles;
+mod schedule;
+pub mod screen;
+pub mod script;
+pub mod sequence;
+pub mod stats;
+pub mod template;
+mod widgets;
 
 pub use app::ClickApp;
+pub use engine::{Clicker, ClickerConfig};
 pub use input::InputHandler;
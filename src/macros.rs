@@ -0,0 +1,380 @@
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::input::{CaptureDevices, CaptureKind, CapturedEvent, InputCapture, MouseButton};
+
+/// A recorded input event and how long after the previous one it happened
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct MacroEvent {
+    pub delay: Duration,
+    pub kind: CaptureKind,
+}
+
+/// Mouse and keyboard input recorded with its original timing
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Macro {
+    pub events: Vec<MacroEvent>,
+}
+
+impl Macro {
+    pub fn from_captured(captured: &[CapturedEvent]) -> Self {
+        let mut last_time = captured.first().map(|event| event.time);
+        let events = captured
+            .iter()
+            .map(|event| {
+                let delay = last_time
+                    .and_then(|last| event.time.duration_since(last).ok())
+                    .unwrap_or_default();
+                last_time = Some(event.time);
+                MacroEvent {
+                    delay,
+                    kind: event.kind,
+                }
+            })
+            .collect();
+
+        Self { events }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.events.iter().map(|event| event.delay).sum()
+    }
+}
+
+/// Slowest and fastest playback relative to the recorded pace
+pub const MIN_SPEED: f64 = 0.25;
+pub const MAX_SPEED: f64 = 10.0;
+
+/// A [`Macro`] together with how to replay it
+#[derive(Clone)]
+pub struct Playback {
+    pub recording: Macro,
+    /// Multiplier on the recorded pace, so 2.0 plays twice as fast
+    pub speed: f64,
+    /// Times to play the macro through, or forever when `None`
+    pub loops: Option<u32>,
+}
+
+/// Steps through a [`Playback`], keeping track of what is held down so
+/// nothing is left stuck when playback stops early
+pub struct MacroPlayer {
+    playback: Playback,
+    position: usize,
+    loops_done: u32,
+    held: Vec<CaptureKind>,
+}
+
+impl MacroPlayer {
+    pub fn new(playback: Playback) -> Self {
+        Self {
+            playback,
+            position: 0,
+            loops_done: 0,
+            held: Vec::new(),
+        }
+    }
+
+    /// Notes that `kind` was sent, keeping track of what is held down
+    pub fn sent(&mut self, kind: CaptureKind) {
+        match press_state(&kind) {
+            Some((_, true)) => self.held.push(kind),
+            Some((target, false)) => self
+                .held
+                .retain(|held| press_state(held).map(|(held, _)| held) != Some(target)),
+            None => {}
+        }
+    }
+
+    /// Everything currently held down, to press again after a pause
+    pub fn held(&self) -> &[CaptureKind] {
+        &self.held
+    }
+
+    /// Releases for everything currently held down
+    pub fn releases(&self) -> impl Iterator<Item = CaptureKind> + '_ {
+        self.held.iter().map(|held| match *held {
+            CaptureKind::Button { button, .. } => CaptureKind::Button {
+                button,
+                pressed: false,
+            },
+            CaptureKind::Key { code, .. } => CaptureKind::Key {
+                code,
+                pressed: false,
+            },
+            other => other,
+        })
+    }
+}
+
+impl Iterator for MacroPlayer {
+    type Item = MacroEvent;
+
+    /// Returns the next event with its delay scaled by the playback speed
+    fn next(&mut self) -> Option<MacroEvent> {
+        let events = &self.playback.recording.events;
+        if events.is_empty() {
+            return None;
+        }
+
+        if self.position == events.len() {
+            self.position = 0;
+            self.loops_done += 1;
+        }
+        if self
+            .playback
+            .loops
+            .is_some_and(|loops| self.loops_done >= loops)
+        {
+            return None;
+        }
+
+        let event = events[self.position];
+        self.position += 1;
+        Some(MacroEvent {
+            delay: event
+                .delay
+                .div_f64(self.playback.speed.clamp(MIN_SPEED, MAX_SPEED)),
+            kind: event.kind,
+        })
+    }
+}
+
+/// Identifies what a press or release acts on, so the two can be paired up
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Pressable {
+    Button(MouseButton),
+    Key(u16),
+}
+
+fn press_state(kind: &CaptureKind) -> Option<(Pressable, bool)> {
+    match *kind {
+        CaptureKind::Button { button, pressed } => Some((Pressable::Button(button), pressed)),
+        CaptureKind::Key { code, pressed } => Some((Pressable::Key(code), pressed)),
+        CaptureKind::Move { .. } | CaptureKind::MoveTo { .. } | CaptureKind::Wheel { .. } => None,
+    }
+}
+
+/// Drops releases of anything pressed before recording began, and presses
+/// still held when it ended, such as the hotkeys that started and stopped it
+fn trim_unpaired(events: &mut Vec<CapturedEvent>) {
+    let mut held = HashSet::new();
+    events.retain(|event| match press_state(&event.kind) {
+        Some((target, true)) => {
+            held.insert(target);
+            true
+        }
+        Some((target, false)) => held.remove(&target),
+        None => true,
+    });
+
+    let mut released = HashSet::new();
+    let mut kept: Vec<CapturedEvent> = events
+        .drain(..)
+        .rev()
+        .filter(|event| match press_state(&event.kind) {
+            Some((target, false)) => {
+                released.insert(target);
+                true
+            }
+            Some((target, true)) => released.remove(&target),
+            None => true,
+        })
+        .collect();
+    kept.reverse();
+    *events = kept;
+}
+
+/// Records physical mouse and keyboard input into a [`Macro`]
+pub struct MacroRecorder {
+    capture: InputCapture,
+    events: Vec<CapturedEvent>,
+    /// Set once the stop key is seen, after which input is no longer kept
+    stopped: bool,
+}
+
+impl MacroRecorder {
+    pub fn start() -> Result<Self> {
+        Ok(Self {
+            capture: InputCapture::start(CaptureDevices::MiceAndKeyboards)?,
+            events: Vec::new(),
+            stopped: false,
+        })
+    }
+
+    /// Pulls in newly captured events, returning true if `stop_key` was pressed
+    ///
+    /// Nothing from the stop key onwards is kept.
+    pub fn poll(&mut self, stop_key: Option<u16>) -> bool {
+        for event in self.capture.drain() {
+            if self.stopped {
+                continue;
+            }
+            if let Some(code) = stop_key
+                && event.kind
+                    == (CaptureKind::Key {
+                        code,
+                        pressed: true,
+                    })
+            {
+                self.stopped = true;
+                continue;
+            }
+            self.events.push(event);
+        }
+        self.stopped
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Ends the recording, leaving out the final left click when the recording
+    /// was stopped by clicking a button
+    pub fn finish(mut self, drop_last_click: bool) -> Macro {
+        self.poll(None);
+        let mut events = self.events;
+
+        if drop_last_click
+            && let Some(press) = events.iter().rposition(|event| {
+                event.kind
+                    == CaptureKind::Button {
+                        button: MouseButton::Left,
+                        pressed: true,
+                    }
+            })
+        {
+            events.truncate(press);
+        }
+
+        trim_unpaired(&mut events);
+        Macro::from_captured(&events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+
+    fn key(code: u16, pressed: bool) -> CaptureKind {
+        CaptureKind::Key { code, pressed }
+    }
+
+    fn left(pressed: bool) -> CaptureKind {
+        CaptureKind::Button {
+            button: MouseButton::Left,
+            pressed,
+        }
+    }
+
+    fn recording() -> Macro {
+        Macro {
+            events: vec![
+                MacroEvent {
+                    delay: Duration::ZERO,
+                    kind: left(true),
+                },
+                MacroEvent {
+                    delay: Duration::from_millis(80),
+                    kind: left(false),
+                },
+                MacroEvent {
+                    delay: Duration::from_millis(300),
+                    kind: key(30, true),
+                },
+            ],
+        }
+    }
+
+    fn trimmed(kinds: &[CaptureKind]) -> Vec<CaptureKind> {
+        let mut events = kinds
+            .iter()
+            .map(|&kind| CapturedEvent {
+                time: SystemTime::UNIX_EPOCH,
+                kind,
+            })
+            .collect();
+        trim_unpaired(&mut events);
+        events.into_iter().map(|event| event.kind).collect()
+    }
+
+    #[test]
+    fn releases_from_before_recording_are_dropped() {
+        let kinds = trimmed(&[key(29, false), left(true), left(false)]);
+        assert_eq!(kinds, [left(true), left(false)]);
+    }
+
+    #[test]
+    fn presses_still_held_at_the_end_are_dropped() {
+        let kinds = trimmed(&[left(true), left(false), key(29, true), key(87, true)]);
+        assert_eq!(kinds, [left(true), left(false)]);
+    }
+
+    #[test]
+    fn moves_and_paired_presses_are_kept() {
+        let all = [
+            key(30, true),
+            CaptureKind::Move { dx: 3, dy: -1 },
+            left(true),
+            key(30, false),
+            left(false),
+        ];
+        assert_eq!(trimmed(&all), all);
+    }
+
+    #[test]
+    fn double_speed_halves_the_delays() {
+        let player = MacroPlayer::new(Playback {
+            recording: recording(),
+            speed: 2.0,
+            loops: Some(1),
+        });
+        let delays: Vec<_> = player.map(|event| event.delay).collect();
+        assert_eq!(
+            delays,
+            [
+                Duration::ZERO,
+                Duration::from_millis(40),
+                Duration::from_millis(150)
+            ]
+        );
+    }
+
+    #[test]
+    fn plays_the_macro_once_per_loop() {
+        let player = MacroPlayer::new(Playback {
+            recording: recording(),
+            speed: 1.0,
+            loops: Some(3),
+        });
+        assert_eq!(player.count(), 9);
+
+        let mut endless = MacroPlayer::new(Playback {
+            recording: recording(),
+            speed: 1.0,
+            loops: None,
+        });
+        assert_eq!(endless.nth(100).map(|event| event.kind), Some(left(false)));
+    }
+
+    #[test]
+    fn releases_whatever_is_still_held() {
+        let mut player = MacroPlayer::new(Playback {
+            recording: recording(),
+            speed: 1.0,
+            loops: None,
+        });
+        player.sent(left(true));
+        player.sent(key(30, true));
+        player.sent(left(false));
+        assert_eq!(player.held(), [key(30, true)]);
+        assert_eq!(player.releases().collect::<Vec<_>>(), [key(30, false)]);
+    }
+}
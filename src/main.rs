@@ -1,8 +1,38 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
 use eframe::egui::{self, TextWrapMode, Vec2};
 
-fn main() -> eframe::Result {
+/// An auto clicker. Opens the window unless told to run headless.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Click without opening the window, until done or stopped with Ctrl-C
+    Run(click::headless::RunArgs),
+}
+
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Some(Command::Run(args)) => click::headless::run(&args),
+        None => match run_gui() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("click: {e}");
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+fn run_gui() -> eframe::Result {
     let size = Vec2::new(420.0, 360.0);
 
     let native_options = eframe::NativeOptions {
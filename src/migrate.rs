@@ -0,0 +1,508 @@
+//! Upgrading saved settings from older versions of Click.
+//!
+//! Settings carry a `version`; those saved before it existed count as version
+//! 0. Loading reads them into a format-neutral tree, runs every migration from
+//! their version up to [`VERSION`] in turn, and only then deserializes them, so
+//! a renamed or retyped field is carried over rather than reset to its default.
+//!
+//! Changing the shape of a saved field means bumping [`VERSION`], adding a
+//! migration to [`MIGRATIONS`] and a file written by the old version to
+//! `tests/settings`.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Map, Value};
+
+use crate::engine::MAX_CPS;
+
+/// Version of the settings written by this build
+pub const VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades settings from version `n` to `n + 1`
+const MIGRATIONS: [Migration; VERSION as usize] = [v0_to_v1];
+
+/// Brings settings of any older version up to [`VERSION`]
+pub fn upgrade(mut settings: Value) -> Result<Value> {
+    let fields = settings
+        .as_object_mut()
+        .context("The settings aren't a table of fields")?;
+    let version = match fields.get("version") {
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .context("The settings version isn't a whole number")?,
+        None => 0,
+    };
+    if version > VERSION {
+        bail!(
+            "The settings are from a newer version of Click (version {version}, this one reads up to {VERSION})"
+        );
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(fields)
+            .with_context(|| format!("Couldn't upgrade the settings from version {from}"))?;
+    }
+    fields.insert("version".to_owned(), VERSION.into());
+    Ok(settings)
+}
+
+/// Settings from before versioning, where `cps` was a whole number until
+/// fractional CPS and is now a float. Version 0 clicked at least once a second
+/// even when it held 0, and nothing clicks faster than [`MAX_CPS`] now.
+fn v0_to_v1(fields: &mut Map<String, Value>) -> Result<()> {
+    if let Some(cps) = fields.get_mut("cps")
+        && cps.is_u64()
+    {
+        let value = cps.as_f64().context("`cps` isn't a number")?;
+        *cps = value.clamp(1.0, MAX_CPS).into();
+    }
+    Ok(())
+}
+
+/// Reads settings as eframe stores them, in [RON](https://github.com/ron-rs/ron).
+///
+/// RON's own generic values drop enum variant names, which the settings need,
+/// so this reads them the way serde_json represents enums: unit variants as
+/// strings and the rest as a table with the variant name as its only key.
+pub fn from_ron(text: &str) -> Result<Value> {
+    let mut reader = RonReader { text, position: 0 };
+    reader.skip_attributes()?;
+    let value = reader.value()?;
+    reader.skip_whitespace();
+    if reader.position < text.len() {
+        return Err(reader.error("unexpected text after the settings"));
+    }
+    Ok(value)
+}
+
+struct RonReader<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> RonReader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        let before = &self.text[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        anyhow!("Line {line}, column {column}: {message}")
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                self.position += comment.find("*/").map_or(trimmed.len(), |end| end + 4);
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Skips `#![enable(...)]` lines, which only change how the file is written
+    fn skip_attributes(&mut self) -> Result<()> {
+        loop {
+            self.skip_whitespace();
+            if !self.rest().starts_with("#!") {
+                return Ok(());
+            }
+            let end = self
+                .rest()
+                .find(']')
+                .ok_or_else(|| self.error("unclosed attribute"))?;
+            self.position += end + 1;
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{expected}`")))
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let starts_well = rest
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_');
+        if length == 0 || !starts_well {
+            return None;
+        }
+        let start = self.position;
+        self.position += length;
+        Some(&self.text[start..self.position])
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('r') if self.rest()[1..].starts_with(['"', '#']) => {
+                self.raw_string().map(Value::String)
+            }
+            Some('\'') => self.character().map(Value::String),
+            Some('[') => self.list(),
+            Some('{') => self.map(),
+            Some('(') => self.parenthesized(),
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => self.number(),
+            Some(_) => self.named(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    /// A bare name: a boolean, `None`, `Some(..)`, or an enum variant
+    fn named(&mut self) -> Result<Value> {
+        let start = self.position;
+        let Some(name) = self.identifier() else {
+            return Err(self.error("expected a value"));
+        };
+        let name = name.to_owned();
+        match name.as_str() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "None" => return Ok(Value::Null),
+            // serde_json can't hold these, and no saved setting is ever one
+            "inf" | "NaN" => return Ok(Value::Null),
+            _ => {}
+        }
+
+        self.skip_whitespace();
+        if self.peek() != Some('(') {
+            return Ok(Value::String(name));
+        }
+        let inner = self.parenthesized()?;
+        if name == "Some" {
+            return Ok(inner);
+        }
+        if inner.is_null() && self.text[start..self.position].ends_with("()") {
+            return Ok(Value::String(name));
+        }
+        Ok(Value::Object(Map::from_iter([(name, inner)])))
+    }
+
+    /// A struct `(name: value, ...)`, a tuple `(a, b)`, a newtype `(a)`, or `()`
+    fn parenthesized(&mut self) -> Result<Value> {
+        self.expect('(')?;
+        if self.eat(')') {
+            return Ok(Value::Null);
+        }
+
+        // Field names are followed by a colon, tuple items aren't
+        let start = self.position;
+        let is_struct = self.identifier().is_some() && self.eat(':');
+        self.position = start;
+
+        if is_struct {
+            let mut fields = Map::new();
+            loop {
+                if self.eat(')') {
+                    return Ok(Value::Object(fields));
+                }
+                let name = self
+                    .identifier()
+                    .ok_or_else(|| self.error("expected a field name"))?
+                    .to_owned();
+                self.expect(':')?;
+                fields.insert(name, self.value()?);
+                if !self.eat(',') {
+                    self.expect(')')?;
+                    return Ok(Value::Object(fields));
+                }
+            }
+        }
+
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        loop {
+            if self.eat(')') {
+                break;
+            }
+            items.push(self.value()?);
+            trailing_comma = self.eat(',');
+            if !trailing_comma {
+                self.expect(')')?;
+                break;
+            }
+        }
+        if items.len() == 1 && !trailing_comma {
+            Ok(items.remove(0))
+        } else {
+            Ok(Value::Array(items))
+        }
+    }
+
+    fn list(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            if self.eat(']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            if !self.eat(',') {
+                self.expect(']')?;
+                return Ok(Value::Array(items));
+            }
+        }
+    }
+
+    fn map(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut entries = Map::new();
+        loop {
+            if self.eat('}') {
+                return Ok(Value::Object(entries));
+            }
+            let key = match self.value()? {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+            self.expect(':')?;
+            entries.insert(key, self.value()?);
+            if !self.eat(',') {
+                self.expect('}')?;
+                return Ok(Value::Object(entries));
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.' | '_')))
+            .unwrap_or(rest.len());
+        let text = rest[..length].replace('_', "");
+        self.position += length;
+
+        let number = if let Ok(integer) = text.parse::<u64>() {
+            Value::from(integer)
+        } else if let Ok(integer) = text.parse::<i64>() {
+            Value::from(integer)
+        } else if let Ok(float) = text.parse::<f64>() {
+            Value::from(float)
+        } else {
+            return Err(self.error(&format!("`{text}` isn't a number")));
+        };
+        Ok(number)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += index + 1;
+                    return Ok(string);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('0') => '\0',
+                        Some('u') => {
+                            let hex: String = chars
+                                .by_ref()
+                                .map(|(_, c)| c)
+                                .skip_while(|&c| c == '{')
+                                .take_while(|&c| c != '}')
+                                .collect();
+                            u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?
+                        }
+                        Some(other) => other,
+                        None => break,
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+        Err(self.error("unclosed string"))
+    }
+
+    fn raw_string(&mut self) -> Result<String> {
+        self.position += 1;
+        let hashes = self.rest().len() - self.rest().trim_start_matches('#').len();
+        self.position += hashes;
+        self.expect('"')?;
+        let end = format!("\"{}", "#".repeat(hashes));
+        let length = self
+            .rest()
+            .find(&end)
+            .ok_or_else(|| self.error("unclosed string"))?;
+        let string = self.rest()[..length].to_owned();
+        self.position += length + end.len();
+        Ok(string)
+    }
+
+    fn character(&mut self) -> Result<String> {
+        self.position += 1;
+        let end = self
+            .rest()
+            .find('\'')
+            .ok_or_else(|| self.error("unclosed character"))?;
+        let character = self.rest()[..end].to_owned();
+        self.position += end + 1;
+        Ok(character)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::ClickApp;
+
+    /// Loads a file from the corpus the way the app would, and returns the
+    /// settings it ends up with
+    fn load(name: &str) -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/settings")
+            .join(name);
+        let text = fs::read_to_string(&path).unwrap();
+        let tree = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => from_ron(&text).unwrap(),
+            Some("toml") => toml::from_str(&text).unwrap(),
+            _ => panic!("{name} isn't RON or TOML"),
+        };
+        let app = ClickApp::from_saved(tree).unwrap_or_else(|e| panic!("{name}: {e:#}"));
+        serde_json::to_value(app).unwrap()
+    }
+
+    #[test]
+    fn every_saved_file_loads() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/settings");
+        for entry in fs::read_dir(directory).unwrap() {
+            let name = entry.unwrap().file_name();
+            let settings = load(name.to_str().unwrap());
+            assert_eq!(settings["version"], VERSION, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn whole_number_cps_becomes_a_float() {
+        let settings = load("v0-baseline.ron");
+        assert_eq!(settings["cps"], json!(35.0));
+        assert_eq!(settings["interval_mode"], "Cps");
+        assert_eq!(settings["mouse_button"], "Right");
+        assert_eq!(settings["click_type"], "Double");
+        assert_eq!(settings["location"], json!({ "x": 640, "y": 360 }));
+        assert_eq!(settings["start_shortcut"]["modifiers"]["ctrl"], true);
+        assert_eq!(settings["start_shortcut"]["logical_key"], "F6");
+    }
+
+    #[test]
+    fn macro_and_learned_timing_are_kept() {
+        let settings = load("v0-macro.ron");
+        assert_eq!(settings["macro_speed"], json!(1.5));
+        assert_eq!(settings["seed"], 9_876_543_210u64);
+        assert_eq!(settings["learned_profile"]["hold_mean_ms"], json!(74.25));
+        assert_eq!(settings["ramp"]["easing"], "EaseOut");
+
+        let events = settings["recorded_macro"]["events"].as_array().unwrap();
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[2]["kind"], json!({ "Move": { "dx": 4, "dy": -2 } }));
+    }
+
+    #[test]
+    fn profiles_and_steps_are_kept() {
+        for name in ["v0-profiles.ron", "v0-config.toml"] {
+            let settings = load(name);
+            assert_eq!(settings["run_mode"], "Sequence", "{name}");
+            assert_eq!(settings["active_profile"], 1, "{name}");
+
+            let profiles = settings["profiles"].as_array().unwrap();
+            assert_eq!(profiles.len(), 2, "{name}");
+            assert_eq!(profiles[1]["name"], "Fast \"farm\"", "{name}");
+            assert_eq!(profiles[1]["shortcut"]["logical_key"], "Num2", "{name}");
+            assert_eq!(profiles[0]["settings"]["jitter"], 15, "{name}");
+
+            let steps = settings["sequence"]["steps"].as_array().unwrap();
+            assert_eq!(steps[0], json!({ "Label": { "name": "start" } }), "{name}");
+            assert_eq!(
+                steps[1]["WaitColor"]["condition"]["color"],
+                json!({ "r": 250, "g": 10, "b": 10 }),
+                "{name}"
+            );
+            assert_eq!(
+                settings["image_target"]["path"], "/home/user/targets/ok-button.png",
+                "{name}"
+            );
+            assert_eq!(settings["image_target"]["search"], "OnceAtStart", "{name}");
+        }
+    }
+
+    #[test]
+    fn whole_number_cps_is_brought_into_range() {
+        for (old, new) in [(0, 1.0), (20, 20.0), (65_535, MAX_CPS)] {
+            let app = ClickApp::from_saved(json!({ "cps": old })).unwrap();
+            assert_eq!(
+                serde_json::to_value(app).unwrap()["cps"],
+                json!(new),
+                "{old}"
+            );
+        }
+    }
+
+    #[test]
+    fn current_settings_survive_a_save() {
+        let app = ClickApp::default();
+        let saved = ron::ser::to_string(&app).unwrap();
+        let loaded = ClickApp::from_saved(from_ron(&saved).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_value(loaded).unwrap(),
+            serde_json::to_value(app).unwrap()
+        );
+    }
+
+    #[test]
+    fn newer_settings_are_refused() {
+        let error = upgrade(json!({ "version": VERSION + 1 })).unwrap_err();
+        assert!(error.to_string().contains("newer version"));
+    }
+
+    #[test]
+    fn ron_errors_point_at_the_text() {
+        let error = from_ron("(\n    cps: 20.0,\n    jitter: ,\n)").unwrap_err();
+        assert_eq!(error.to_string(), "Line 3, column 13: expected a value");
+    }
+}
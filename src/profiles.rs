@@ -0,0 +1,157 @@
+//! Named sets of click settings, for switching between setups without
+//! re-entering them
+
+use eframe::egui::KeyboardShortcut;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::{ClickAction, Coordinates, MouseButton},
+    interval::{IntervalMode, Ramp, TimeInterval},
+    schedule::TimeOfDay,
+};
+
+/// Name of the profile new installs start with
+pub const DEFAULT_NAME: &str = "Default";
+
+/// The settings a profile bundles: how fast, where and what to click, and when
+/// to stop
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProfileSettings {
+    pub interval_mode: IntervalMode,
+    pub time_interval: TimeInterval,
+    pub cps: f64,
+    pub ramp: Ramp,
+    pub use_ramp: bool,
+    pub jitter: u16,
+    pub use_jitter: bool,
+    pub mouse_button: MouseButton,
+    pub click_type: ClickAction,
+    pub location: Coordinates,
+    pub use_location: bool,
+    pub num_clicks: u32,
+    pub use_num_clicks: bool,
+    pub num_bursts: u32,
+    pub use_num_bursts: bool,
+    pub run_duration: TimeInterval,
+    pub use_run_duration: bool,
+    pub stop_at: TimeOfDay,
+    pub use_stop_at: bool,
+}
+
+impl Default for ProfileSettings {
+    fn default() -> Self {
+        Self {
+            interval_mode: IntervalMode::Time,
+            time_interval: TimeInterval::default(),
+            cps: 20.0,
+            ramp: Ramp::default(),
+            use_ramp: false,
+            jitter: 0,
+            use_jitter: false,
+            mouse_button: MouseButton::Left,
+            click_type: ClickAction::Single,
+            location: Coordinates::default(),
+            use_location: false,
+            num_clicks: 100,
+            use_num_clicks: false,
+            num_bursts: 10,
+            use_num_bursts: false,
+            run_duration: TimeInterval {
+                minutes: 1,
+                milliseconds: 0,
+                ..TimeInterval::default()
+            },
+            use_run_duration: false,
+            stop_at: TimeOfDay::default(),
+            use_stop_at: false,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    pub settings: ProfileSettings,
+    /// Switches to this profile while the window has focus
+    pub shortcut: Option<KeyboardShortcut>,
+    /// Also starts clicking when switched to with the hotkey
+    pub start_on_shortcut: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new(DEFAULT_NAME.to_owned(), ProfileSettings::default())
+    }
+}
+
+impl Profile {
+    pub fn new(name: String, settings: ProfileSettings) -> Self {
+        Self {
+            name,
+            settings,
+            shortcut: None,
+            start_on_shortcut: false,
+        }
+    }
+}
+
+/// Returns `base`, or `base (2)`, `base (3)`, ... if a profile already has that
+/// name. A `base` that already ends in a number counts on from it.
+pub fn unique_name(profiles: &[Profile], base: &str) -> String {
+    let taken = |name: &str| profiles.iter().any(|profile| profile.name == name);
+    if !taken(base) {
+        return base.to_owned();
+    }
+
+    let numbered = base
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+        .and_then(|(stem, number)| Some((stem, number.parse::<u32>().ok()?)))
+        .filter(|&(_, number)| number >= 2);
+    let (stem, first) = match numbered {
+        Some((stem, number)) => (stem, number + 1),
+        None => (base, 2),
+    };
+    (first..)
+        .map(|number| format!("{stem} ({number})"))
+        .find(|name| !taken(name))
+        .unwrap_or_else(|| base.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(names: &[&str]) -> Vec<Profile> {
+        names
+            .iter()
+            .map(|name| Profile::new((*name).to_owned(), ProfileSettings::default()))
+            .collect()
+    }
+
+    #[test]
+    fn free_names_are_kept() {
+        assert_eq!(unique_name(&named(&["Default"]), "Fast"), "Fast");
+    }
+
+    #[test]
+    fn clashing_names_get_the_next_free_number() {
+        assert_eq!(unique_name(&named(&["Fast"]), "Fast"), "Fast (2)");
+        assert_eq!(
+            unique_name(&named(&["Fast", "Fast (2)", "Fast (4)"]), "Fast"),
+            "Fast (3)"
+        );
+    }
+
+    #[test]
+    fn numbered_names_count_on_from_their_number() {
+        let profiles = named(&["Fast", "Fast (2)"]);
+        assert_eq!(unique_name(&profiles, "Fast (2)"), "Fast (3)");
+
+        let profiles = named(&["Take (1)", "Ratio (1:2)"]);
+        assert_eq!(unique_name(&profiles, "Take (1)"), "Take (1) (2)");
+        assert_eq!(unique_name(&profiles, "Ratio (1:2)"), "Ratio (1:2) (2)");
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use chrono::{Local, NaiveDateTime, NaiveTime, TimeDelta};
+
+/// A wall-clock time in the local timezone, without a date
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct TimeOfDay {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl TimeOfDay {
+    /// Returns how long from now until the local clock next reads this time,
+    /// looking no earlier than `after` from now so a stop time can follow a delayed start
+    pub fn next_after(self, after: Duration) -> Duration {
+        self.next_after_from(Local::now().naive_local(), after)
+    }
+
+    fn next_after_from(self, now: NaiveDateTime, after: Duration) -> Duration {
+        let Some(target) =
+            NaiveTime::from_hms_opt(self.hours as u32, self.minutes as u32, self.seconds as u32)
+        else {
+            return after;
+        };
+
+        let now = now + TimeDelta::from_std(after).unwrap_or_default();
+        let mut wait = target - now.time();
+        if wait <= TimeDelta::zero() {
+            wait += TimeDelta::days(1);
+        }
+
+        after + wait.to_std().unwrap_or_default()
+    }
+}
+
+/// Formats a countdown as `H:MM:SS`
+pub fn format_countdown(remaining: Duration) -> String {
+    let total = remaining.as_secs_f64().ceil() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        total / 3600,
+        (total / 60) % 60,
+        total % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn at(hours: u8, minutes: u8, seconds: u8) -> TimeOfDay {
+        TimeOfDay {
+            hours,
+            minutes,
+            seconds,
+        }
+    }
+
+    /// 14:30:00 on an ordinary day
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, 10)
+            .unwrap()
+            .and_hms_opt(14, 30, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn later_today_waits_until_then() {
+        let wait = at(17, 45, 30).next_after_from(now(), Duration::ZERO);
+        assert_eq!(wait, HOUR * 3 + Duration::from_secs(15 * 60 + 30));
+    }
+
+    #[test]
+    fn earlier_times_roll_over_to_tomorrow() {
+        let wait = at(9, 0, 0).next_after_from(now(), Duration::ZERO);
+        assert_eq!(wait, HOUR * 18 + Duration::from_secs(30 * 60));
+    }
+
+    #[test]
+    fn the_current_time_waits_a_whole_day() {
+        let wait = at(14, 30, 0).next_after_from(now(), Duration::ZERO);
+        assert_eq!(wait, HOUR * 24);
+    }
+
+    #[test]
+    fn stop_times_come_after_a_delayed_start() {
+        // Starting at 16:30, a 15:00 stop means tomorrow, not half an hour ago
+        let wait = at(15, 0, 0).next_after_from(now(), HOUR * 2);
+        assert_eq!(wait, HOUR * 2 + HOUR * 22 + Duration::from_secs(30 * 60));
+
+        let wait = at(17, 0, 0).next_after_from(now(), HOUR * 2);
+        assert_eq!(wait, HOUR * 2 + Duration::from_secs(30 * 60));
+    }
+
+    #[test]
+    fn invalid_times_wait_only_for_the_start() {
+        assert_eq!(at(25, 0, 0).next_after_from(now(), HOUR), HOUR);
+    }
+
+    #[test]
+    fn countdowns_round_up_to_whole_seconds() {
+        assert_eq!(format_countdown(Duration::ZERO), "0:00:00");
+        assert_eq!(format_countdown(Duration::from_millis(1)), "0:00:01");
+        assert_eq!(format_countdown(Duration::from_secs(59)), "0:00:59");
+        assert_eq!(
+            format_countdown(HOUR * 26 + Duration::from_secs(61)),
+            "26:01:01"
+        );
+    }
+}
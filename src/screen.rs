@@ -0,0 +1,474 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::input::Coordinates;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    /// Largest difference between any one channel of the two colors
+    pub fn distance(self, other: Color) -> u8 {
+        [
+            self.r.abs_diff(other.r),
+            self.g.abs_diff(other.g),
+            self.b.abs_diff(other.b),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default()
+    }
+}
+
+/// Pixels read from a rectangle of the screen, row by row
+pub struct Image {
+    /// Screen position of the top left pixel
+    pub origin: Coordinates,
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub(crate) fn new(origin: Coordinates, width: u32, height: u32, pixels: Vec<Color>) -> Self {
+        debug_assert_eq!(pixels.len(), (width * height) as usize);
+        Self {
+            origin,
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+}
+
+/// A connection for reading what's on screen and where the pointer is
+pub struct Screen {
+    platform: platform::Screen,
+}
+
+impl Screen {
+    pub fn connect() -> Result<Self> {
+        Ok(Self {
+            platform: platform::Screen::connect()?,
+        })
+    }
+
+    pub fn pointer(&self) -> Result<Coordinates> {
+        self.platform.pointer()
+    }
+
+    /// Size of the whole screen in pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.platform.size()
+    }
+
+    /// Reads the part of the rectangle at `origin` that's on screen, which may
+    /// be empty
+    pub fn capture(&self, origin: Coordinates, width: u32, height: u32) -> Result<Image> {
+        let (screen_width, screen_height) = self.platform.size();
+        let left = origin.x.clamp(0, screen_width as i32);
+        let top = origin.y.clamp(0, screen_height as i32);
+        let right = (origin.x + width as i32).clamp(left, screen_width as i32);
+        let bottom = (origin.y + height as i32).clamp(top, screen_height as i32);
+
+        let origin = Coordinates { x: left, y: top };
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+        if width == 0 || height == 0 {
+            return Ok(Image::new(origin, width, height, Vec::new()));
+        }
+
+        let pixels = self.platform.capture(origin, width, height)?;
+        Ok(Image::new(origin, width, height, pixels))
+    }
+
+    /// Reads a single pixel, which must be on screen
+    pub fn pixel(&self, at: Coordinates) -> Result<Color> {
+        self.capture(at, 1, 1)?
+            .pixels()
+            .first()
+            .copied()
+            .context("The point is off screen")
+    }
+}
+
+/// Waits for, or only clicks while, a point on screen has a certain color
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PixelCondition {
+    pub location: Coordinates,
+    /// Pixels checked on every side of `location`, so a match anywhere in the
+    /// square counts; 0 checks just the one pixel
+    pub radius: u16,
+    pub color: Color,
+    /// Largest difference in any channel that still counts as the same color
+    pub tolerance: u8,
+}
+
+impl Default for PixelCondition {
+    fn default() -> Self {
+        Self {
+            location: Coordinates::default(),
+            radius: 0,
+            color: Color { r: 0, g: 200, b: 0 },
+            tolerance: 16,
+        }
+    }
+}
+
+impl PixelCondition {
+    pub fn is_met(&self, screen: &Screen) -> Result<bool> {
+        let radius = self.radius as i32;
+        let side = radius as u32 * 2 + 1;
+        let image = screen.capture(
+            Coordinates {
+                x: self.location.x - radius,
+                y: self.location.y - radius,
+            },
+            side,
+            side,
+        )?;
+        Ok(self.is_met_in(&image))
+    }
+
+    /// Whether any pixel of `image`, captured around the location, is close
+    /// enough to the color
+    fn is_met_in(&self, image: &Image) -> bool {
+        image
+            .pixels()
+            .iter()
+            .any(|pixel| pixel.distance(self.color) <= self.tolerance)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::{Context, Result, bail};
+    use x11rb::{
+        connection::Connection,
+        protocol::xproto::{ConnectionExt, ImageFormat, ImageOrder, Window},
+        rust_connection::RustConnection,
+    };
+
+    use super::Color;
+    use crate::input::Coordinates;
+
+    /// Where each channel sits in a pixel value
+    struct Channel {
+        mask: u32,
+        shift: u32,
+    }
+
+    impl Channel {
+        fn new(mask: u32) -> Self {
+            Self {
+                mask,
+                shift: mask.trailing_zeros(),
+            }
+        }
+
+        /// Reads the channel scaled to 8 bits, whatever its depth
+        fn read(&self, value: u32) -> u8 {
+            let max = self.mask >> self.shift;
+            if max == 0 {
+                return 0;
+            }
+            (((value & self.mask) >> self.shift) * 255 / max) as u8
+        }
+    }
+
+    /// Reads the root window with core X11 requests, which also works under
+    /// XWayland for X11 windows
+    pub struct Screen {
+        connection: RustConnection,
+        root: Window,
+        width: u16,
+        height: u16,
+        bytes_per_pixel: usize,
+        lsb_first: bool,
+        red: Channel,
+        green: Channel,
+        blue: Channel,
+    }
+
+    impl Screen {
+        pub fn connect() -> Result<Self> {
+            let (connection, screen_num) = x11rb::connect(None)
+                .context("Couldn't connect to the X server to read the screen")?;
+            let setup = connection.setup();
+            let screen = &setup.roots[screen_num];
+
+            let visual = screen
+                .allowed_depths
+                .iter()
+                .flat_map(|depth| &depth.visuals)
+                .find(|visual| visual.visual_id == screen.root_visual)
+                .context("The X server didn't describe its root visual")?;
+            let bits_per_pixel = setup
+                .pixmap_formats
+                .iter()
+                .find(|format| format.depth == screen.root_depth)
+                .map(|format| format.bits_per_pixel)
+                .context("The X server didn't describe its pixel format")?;
+            if bits_per_pixel != 24 && bits_per_pixel != 32 {
+                bail!("Reading a {bits_per_pixel}-bit screen isn't supported");
+            }
+
+            Ok(Self {
+                root: screen.root,
+                width: screen.width_in_pixels,
+                height: screen.height_in_pixels,
+                bytes_per_pixel: bits_per_pixel as usize / 8,
+                lsb_first: setup.image_byte_order == ImageOrder::LSB_FIRST,
+                red: Channel::new(visual.red_mask),
+                green: Channel::new(visual.green_mask),
+                blue: Channel::new(visual.blue_mask),
+                connection,
+            })
+        }
+
+        pub fn size(&self) -> (u32, u32) {
+            (self.width as u32, self.height as u32)
+        }
+
+        pub fn pointer(&self) -> Result<Coordinates> {
+            let reply = self.connection.query_pointer(self.root)?.reply()?;
+            Ok(Coordinates {
+                x: reply.root_x as i32,
+                y: reply.root_y as i32,
+            })
+        }
+
+        /// Reads a rectangle that lies entirely on screen
+        pub fn capture(&self, origin: Coordinates, width: u32, height: u32) -> Result<Vec<Color>> {
+            let reply = self
+                .connection
+                .get_image(
+                    ImageFormat::Z_PIXMAP,
+                    self.root,
+                    origin.x as i16,
+                    origin.y as i16,
+                    width as u16,
+                    height as u16,
+                    !0,
+                )?
+                .reply()?;
+
+            // Rows are padded, so work out their length from what came back
+            let stride = reply.data.len() / height as usize;
+            let mut pixels = Vec::with_capacity((width * height) as usize);
+            for row in reply.data.chunks_exact(stride) {
+                for bytes in
+                    row[..width as usize * self.bytes_per_pixel].chunks_exact(self.bytes_per_pixel)
+                {
+                    let value = if self.lsb_first {
+                        bytes
+                            .iter()
+                            .rev()
+                            .fold(0, |value, &byte| value << 8 | byte as u32)
+                    } else {
+                        bytes
+                            .iter()
+                            .fold(0, |value, &byte| value << 8 | byte as u32)
+                    };
+                    pixels.push(Color {
+                        r: self.red.read(value),
+                        g: self.green.read(value),
+                        b: self.blue.read(value),
+                    });
+                }
+            }
+
+            Ok(pixels)
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod platform {
+    use anyhow::{Result, bail};
+
+    use super::Color;
+    use crate::input::Coordinates;
+
+    /// Can't be created, as reading the screen only works on X11 so far
+    pub enum Screen {}
+
+    impl Screen {
+        pub fn connect() -> Result<Self> {
+            bail!("Reading the screen is only supported on Linux (X11) for now")
+        }
+
+        pub fn size(&self) -> (u32, u32) {
+            match *self {}
+        }
+
+        pub fn pointer(&self) -> Result<Coordinates> {
+            match *self {}
+        }
+
+        pub fn capture(
+            &self,
+            _origin: Coordinates,
+            _width: u32,
+            _height: u32,
+        ) -> Result<Vec<Color>> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREEN: Color = Color { r: 0, g: 200, b: 0 };
+
+    fn condition(tolerance: u8) -> PixelCondition {
+        PixelCondition {
+            color: GREEN,
+            tolerance,
+            ..PixelCondition::default()
+        }
+    }
+
+    fn image(pixels: Vec<Color>) -> Image {
+        let side = (pixels.len() as f64).sqrt() as u32;
+        Image::new(Coordinates::default(), side, side, pixels)
+    }
+
+    #[test]
+    fn distance_is_the_largest_channel_difference() {
+        let color = Color {
+            r: 10,
+            g: 200,
+            b: 30,
+        };
+        assert_eq!(color.distance(color), 0);
+        assert_eq!(color.distance(Color { r: 0, g: 0, b: 0 }), 200);
+        assert_eq!(
+            color.distance(Color {
+                r: 15,
+                g: 190,
+                b: 37,
+            }),
+            10
+        );
+        assert_eq!(
+            Color { r: 0, g: 0, b: 0 }.distance(Color {
+                r: 255,
+                g: 255,
+                b: 255,
+            }),
+            255
+        );
+    }
+
+    #[test]
+    fn tolerance_is_inclusive() {
+        let near = Color {
+            r: 16,
+            g: 190,
+            b: 0,
+        };
+        assert!(condition(16).is_met_in(&image(vec![near])));
+        assert!(!condition(15).is_met_in(&image(vec![near])));
+        assert!(condition(0).is_met_in(&image(vec![GREEN])));
+    }
+
+    #[test]
+    fn any_pixel_in_the_square_counts() {
+        let black = Color::default();
+        let mut pixels = vec![black; 9];
+        assert!(!condition(16).is_met_in(&image(pixels.clone())));
+
+        pixels[8] = GREEN;
+        assert!(condition(16).is_met_in(&image(pixels)));
+        // Nothing captured, such as a point off screen, never matches
+        assert!(!condition(255).is_met_in(&image(Vec::new())));
+    }
+
+    /// Paints a window in a known color and reads it back with GetImage
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs an X server, run with `xvfb-run cargo test -- --ignored`"]
+    fn reads_the_screen() {
+        use std::{
+            thread,
+            time::{Duration, Instant},
+        };
+
+        use x11rb::{
+            connection::Connection,
+            protocol::xproto::{ConnectionExt, CreateWindowAux, WindowClass},
+        };
+
+        let (connection, screen_num) = x11rb::connect(None).unwrap();
+        let x_screen = &connection.setup().roots[screen_num];
+        let window = connection.generate_id().unwrap();
+        connection
+            .create_window(
+                x_screen.root_depth,
+                window,
+                x_screen.root,
+                10,
+                20,
+                50,
+                40,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x_screen.root_visual,
+                // The usual 24-bit visual keeps red, green and blue in that order
+                &CreateWindowAux::new().background_pixel(0x00c8_6432),
+            )
+            .unwrap();
+        connection.map_window(window).unwrap();
+        connection.flush().unwrap();
+
+        let screen = Screen::connect().unwrap();
+        let painted = Color {
+            r: 200,
+            g: 100,
+            b: 50,
+        };
+        let inside = Coordinates { x: 30, y: 40 };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while screen.pixel(inside).unwrap() != painted {
+            assert!(Instant::now() < deadline, "the window never showed up");
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let image = screen
+            .capture(Coordinates { x: 10, y: 20 }, 50, 40)
+            .unwrap();
+        assert_eq!((image.width, image.height), (50, 40));
+        assert!(image.pixels().iter().all(|pixel| *pixel == painted));
+
+        // Only the part that's on screen is read
+        let image = screen
+            .capture(Coordinates { x: -5, y: -5 }, 10, 10)
+            .unwrap();
+        assert_eq!(image.origin, Coordinates { x: 0, y: 0 });
+        assert_eq!(image.pixels().len(), 25);
+
+        let condition = PixelCondition {
+            location: Coordinates { x: 5, y: 15 },
+            radius: 5,
+            color: painted,
+            tolerance: 0,
+        };
+        assert!(condition.is_met(&screen).unwrap());
+        let condition = PixelCondition {
+            radius: 4,
+            ..condition
+        };
+        assert!(!condition.is_met(&screen).unwrap());
+    }
+}
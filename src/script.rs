@@ -0,0 +1,455 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Position, Scope};
+use tokio::sync::mpsc;
+
+use crate::{
+    InputHandler,
+    engine::Event,
+    input::{CaptureKind, ClickAction, Coordinates, MouseButton},
+    interval::Jitter,
+    keys,
+    stats::ClickSample,
+};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Starting point for a new script
+pub const EXAMPLE: &str = "\
+// Runs again each time it reaches the end; `iteration` counts finished runs
+click(\"left\");
+sleep(random(80, 120));
+";
+
+/// Functions available to scripts, shown as a reference next to the editor
+pub const REFERENCE: &str = "\
+click()  click(\"left\" | \"right\" | \"middle\")  uses the click type from Settings
+double_click()  double_click(button)
+move_to(x, y)
+sleep(ms)  waits, with the random delay from Settings applied
+key(\"A\")  key(code)  taps a key by name or Linux key code
+random(min, max)  whole number in min..=max
+random()  number in 0..1
+clicks()  clicks so far this run
+iteration  finished runs of the script
+stop()  ends the run";
+
+/// A script that compiled without syntax errors
+#[derive(Clone)]
+pub struct Script {
+    ast: AST,
+}
+
+impl Script {
+    pub fn compile(source: &str) -> Result<Self> {
+        let ast = Engine::new()
+            .compile(source)
+            .map_err(|e| anyhow!("Syntax error: {e}"))?;
+        Ok(Self { ast })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ControlState {
+    Running,
+    Paused,
+    Stopped,
+}
+
+/// Pause and stop requests from the clicker task to the thread running a script
+pub(crate) struct ScriptControl {
+    state: Mutex<ControlState>,
+    changed: Condvar,
+}
+
+impl ScriptControl {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(ControlState::Running),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn pause(&self) {
+        self.set(ControlState::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.set(ControlState::Running);
+    }
+
+    pub fn stop(&self) {
+        self.set(ControlState::Stopped);
+    }
+
+    fn set(&self, state: ControlState) {
+        let mut current = self.lock();
+        // Stopping is final
+        if *current != ControlState::Stopped {
+            *current = state;
+        }
+        self.changed.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ControlState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn is_stopped(&self) -> bool {
+        *self.lock() == ControlState::Stopped
+    }
+
+    /// Blocks while paused, returning false once stopped
+    fn checkpoint(&self) -> bool {
+        let mut state = self.lock();
+        while *state == ControlState::Paused {
+            state = self
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *state == ControlState::Running
+    }
+
+    /// Sleeps for `duration` of unpaused time, returning how long it was
+    /// paused for, or `None` if stopped
+    fn sleep(&self, duration: Duration) -> Option<Duration> {
+        let mut remaining = duration;
+        let mut paused_for = Duration::ZERO;
+        let mut state = self.lock();
+
+        loop {
+            let started = Instant::now();
+            match *state {
+                ControlState::Stopped => return None,
+                ControlState::Paused => {
+                    state = self
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    paused_for += started.elapsed();
+                }
+                ControlState::Running if remaining.is_zero() => return Some(paused_for),
+                ControlState::Running => {
+                    state = self
+                        .changed
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                    remaining = remaining.saturating_sub(started.elapsed());
+                }
+            }
+        }
+    }
+}
+
+/// Settings from the clicker that a script runs with
+pub(crate) struct ScriptOptions {
+    /// Times to run the script through, or until stopped when `None`
+    pub runs: Option<u32>,
+    /// Button and action used by `click()` without arguments
+    pub button: MouseButton,
+    pub action: ClickAction,
+    pub click_limit: Option<u32>,
+    /// Random delay applied to `sleep`, with its amount in milliseconds
+    pub jitter: Option<(Jitter, u16)>,
+    pub seed: u64,
+}
+
+/// How a script run ended, when it didn't fail
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum ScriptEnd {
+    Finished,
+    Stopped,
+}
+
+struct State {
+    jitter: Option<(Jitter, u16)>,
+    rng: StdRng,
+    clicks: u32,
+    /// When the next action was due, for the drift statistics
+    due: Instant,
+}
+
+/// Everything the functions exposed to a script share
+#[derive(Clone)]
+struct Context {
+    input: Arc<Mutex<InputHandler>>,
+    control: Arc<ScriptControl>,
+    samples: mpsc::Sender<Event>,
+    state: Arc<Mutex<State>>,
+    button: MouseButton,
+    action: ClickAction,
+    click_limit: Option<u32>,
+}
+
+/// Ends the script without it counting as an error
+fn terminate() -> Box<EvalAltResult> {
+    EvalAltResult::ErrorTerminated(Dynamic::UNIT, Position::NONE).into()
+}
+
+fn parse_button(name: &str) -> ScriptResult<MouseButton> {
+    MouseButton::all()
+        .into_iter()
+        .find(|button| button.to_string().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Unknown mouse button \"{name}\"").into())
+}
+
+impl Context {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_input(&self, f: impl FnOnce(&mut InputHandler) -> Result<()>) -> ScriptResult<()> {
+        let mut handler = self.input.lock().map_err(|e| format!("Lock error: {e}"))?;
+        f(&mut handler).map_err(|e| e.to_string().into())
+    }
+
+    /// Clicks `button`, or the button from Settings, with `action` or the
+    /// click type from Settings
+    fn click(&self, button: Option<&str>, action: Option<ClickAction>) -> ScriptResult<()> {
+        let button = match button {
+            Some(name) => parse_button(name)?,
+            None => self.button,
+        };
+        let action = action.unwrap_or(self.action);
+
+        let mut state = self.state();
+        if self.click_limit.is_some_and(|limit| state.clicks >= limit) {
+            return Err(terminate());
+        }
+
+        self.with_input(|handler| handler.click(button, action))?;
+        state.clicks += 1;
+        let _ = self.samples.try_send(Event::Clicked(ClickSample {
+            count: state.clicks as u64,
+            scheduled: state.due,
+            clicked: Instant::now(),
+        }));
+        state.due = Instant::now();
+        Ok(())
+    }
+
+    fn sleep(&self, ms: f64) -> ScriptResult<()> {
+        let delay = {
+            let mut state = self.state();
+            let base = Duration::from_secs_f64(ms.max(0.0) / 1000.0);
+            let delay = match &mut state.jitter {
+                Some((jitter, amount)) => jitter.next(base, *amount),
+                None => base,
+            };
+            state.due = Instant::now() + delay;
+            delay
+        };
+
+        let paused_for = self.control.sleep(delay).ok_or_else(terminate)?;
+        self.state().due += paused_for;
+        Ok(())
+    }
+
+    fn key(&self, code: u16) -> ScriptResult<()> {
+        self.with_input(|handler| {
+            handler.send(CaptureKind::Key {
+                code,
+                pressed: true,
+            })?;
+            handler.send(CaptureKind::Key {
+                code,
+                pressed: false,
+            })
+        })
+    }
+}
+
+fn build_engine(context: &Context) -> Engine {
+    let mut engine = Engine::new();
+
+    let control = Arc::clone(&context.control);
+    engine.on_progress(move |_| (!control.checkpoint()).then_some(Dynamic::UNIT));
+
+    let ctx = context.clone();
+    engine.register_fn("click", move || ctx.click(None, None));
+    let ctx = context.clone();
+    engine.register_fn("click", move |button: &str| ctx.click(Some(button), None));
+    let ctx = context.clone();
+    engine.register_fn("double_click", move || {
+        ctx.click(None, Some(ClickAction::Double))
+    });
+    let ctx = context.clone();
+    engine.register_fn("double_click", move |button: &str| {
+        ctx.click(Some(button), Some(ClickAction::Double))
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("move_to", move |x: i64, y: i64| {
+        ctx.with_input(|handler| {
+            handler.move_to(Coordinates {
+                x: x as i32,
+                y: y as i32,
+            })
+        })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("sleep", move |ms: i64| ctx.sleep(ms as f64));
+    let ctx = context.clone();
+    engine.register_fn("sleep", move |ms: f64| ctx.sleep(ms));
+
+    let ctx = context.clone();
+    engine.register_fn("key", move |name: &str| -> ScriptResult<()> {
+        let code = keys::KEYS
+            .iter()
+            .find(|(_, key)| key.eq_ignore_ascii_case(name))
+            .map(|(code, _)| *code)
+            .ok_or_else(|| format!("Unknown key \"{name}\""))?;
+        ctx.key(code)
+    });
+    let ctx = context.clone();
+    engine.register_fn("key", move |code: i64| -> ScriptResult<()> {
+        let code = u16::try_from(code).map_err(|_| format!("Invalid key code {code}"))?;
+        ctx.key(code)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("random", move |min: i64, max: i64| {
+        ctx.state().rng.random_range(min.min(max)..=min.max(max))
+    });
+    let ctx = context.clone();
+    engine.register_fn("random", move || ctx.state().rng.random::<f64>());
+
+    let ctx = context.clone();
+    engine.register_fn("clicks", move || ctx.state().clicks as i64);
+    engine.register_fn("stop", || -> ScriptResult<()> { Err(terminate()) });
+
+    engine
+}
+
+/// Runs `script` until it has been through all its runs, calls `stop()`, hits
+/// the click limit or is stopped through `control`. Meant for a blocking thread.
+pub(crate) fn run(
+    script: Script,
+    options: ScriptOptions,
+    input: Arc<Mutex<InputHandler>>,
+    control: Arc<ScriptControl>,
+    samples: mpsc::Sender<Event>,
+) -> Result<ScriptEnd> {
+    let context = Context {
+        input,
+        control: Arc::clone(&control),
+        samples,
+        state: Arc::new(Mutex::new(State {
+            jitter: options.jitter,
+            rng: StdRng::seed_from_u64(options.seed),
+            clicks: 0,
+            due: Instant::now(),
+        })),
+        button: options.button,
+        action: options.action,
+        click_limit: options.click_limit,
+    };
+    let engine = build_engine(&context);
+
+    let mut scope = Scope::new();
+    scope.push("iteration", 0_i64);
+    // Variables a run declares are dropped before the next one
+    let base = scope.len();
+
+    let mut iteration = 0u32;
+    while options.runs.is_none_or(|runs| iteration < runs) {
+        // A script that never sleeps or clicks would otherwise never see a stop
+        if !control.checkpoint() {
+            return Ok(ScriptEnd::Stopped);
+        }
+        scope.set_value("iteration", iteration as i64);
+        match engine.run_ast_with_scope(&mut scope, &script.ast) {
+            Ok(()) => {}
+            Err(e) if matches!(*e, EvalAltResult::ErrorTerminated(..)) => {
+                return Ok(if control.is_stopped() {
+                    ScriptEnd::Stopped
+                } else {
+                    ScriptEnd::Finished
+                });
+            }
+            Err(e) => return Err(anyhow!("Script error: {e}")),
+        }
+        scope.rewind(base);
+        iteration += 1;
+    }
+
+    Ok(ScriptEnd::Finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputBackend;
+
+    /// Accepts input without sending it anywhere
+    struct NoInput;
+
+    impl InputBackend for NoInput {
+        fn click(&mut self, _: MouseButton) -> Result<()> {
+            Ok(())
+        }
+
+        fn press(&mut self, _: MouseButton) -> Result<()> {
+            Ok(())
+        }
+
+        fn release(&mut self, _: MouseButton) -> Result<()> {
+            Ok(())
+        }
+
+        fn move_to(&mut self, _: Coordinates) -> Result<()> {
+            Ok(())
+        }
+
+        fn move_by(&mut self, _: i32, _: i32) -> Result<()> {
+            Ok(())
+        }
+
+        fn scroll(&mut self, _: i32, _: i32) -> Result<()> {
+            Ok(())
+        }
+
+        fn key(&mut self, _: u16, _: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_an_endless_script_that_does_nothing() {
+        let control = Arc::new(ScriptControl::new());
+        let (samples, _events) = mpsc::channel(16);
+        let options = ScriptOptions {
+            runs: None,
+            button: MouseButton::Left,
+            action: ClickAction::Single,
+            click_limit: None,
+            jitter: None,
+            seed: 0,
+        };
+        let input = Arc::new(Mutex::new(InputHandler::with_backend(NoInput)));
+
+        let running = std::thread::spawn({
+            let control = Arc::clone(&control);
+            move || {
+                run(
+                    Script::compile("").unwrap(),
+                    options,
+                    input,
+                    control,
+                    samples,
+                )
+            }
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        control.stop();
+        assert!(matches!(running.join().unwrap(), Ok(ScriptEnd::Stopped)));
+    }
+}
@@ -0,0 +1,497 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use anyhow::{Result, bail};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::{ClickAction, Coordinates, MouseButton},
+    screen::PixelCondition,
+};
+
+/// Control steps run back to back without an action before the sequence is
+/// considered stuck in an empty loop
+const MAX_CONTROL_STEPS: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Step {
+    Click {
+        button: MouseButton,
+        action: ClickAction,
+        use_location: bool,
+        location: Coordinates,
+    },
+    Move {
+        location: Coordinates,
+    },
+    /// Waits `millis`, or a random time between it and `max_millis`
+    Wait {
+        millis: u32,
+        use_random: bool,
+        max_millis: u32,
+    },
+    /// Waits until a point on screen has the given color
+    WaitColor {
+        condition: PixelCondition,
+    },
+    /// Taps the key with the given Linux input event code
+    Key {
+        code: u16,
+    },
+    /// Runs the steps up to the matching [`Step::EndRepeat`] `times` times
+    Repeat {
+        times: u32,
+    },
+    EndRepeat,
+    Label {
+        name: String,
+    },
+    Jump {
+        label: String,
+    },
+}
+
+/// Kinds of step, as offered when adding one to a sequence
+#[derive(Clone, Copy, PartialEq)]
+pub enum StepKind {
+    Click,
+    Move,
+    Wait,
+    WaitColor,
+    Key,
+    Repeat,
+    EndRepeat,
+    Label,
+    Jump,
+}
+
+impl fmt::Display for StepKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepKind::Click => write!(f, "Click"),
+            StepKind::Move => write!(f, "Move"),
+            StepKind::Wait => write!(f, "Wait"),
+            StepKind::WaitColor => write!(f, "Wait for color"),
+            StepKind::Key => write!(f, "Key"),
+            StepKind::Repeat => write!(f, "Repeat"),
+            StepKind::EndRepeat => write!(f, "End repeat"),
+            StepKind::Label => write!(f, "Label"),
+            StepKind::Jump => write!(f, "Jump"),
+        }
+    }
+}
+
+impl StepKind {
+    pub fn all() -> [StepKind; 9] {
+        [
+            StepKind::Click,
+            StepKind::Move,
+            StepKind::Wait,
+            StepKind::WaitColor,
+            StepKind::Key,
+            StepKind::Repeat,
+            StepKind::EndRepeat,
+            StepKind::Label,
+            StepKind::Jump,
+        ]
+    }
+
+    /// Returns a new step of this kind with sensible defaults
+    pub fn to_step(self) -> Step {
+        match self {
+            StepKind::Click => Step::Click {
+                button: MouseButton::Left,
+                action: ClickAction::Single,
+                use_location: false,
+                location: Coordinates::default(),
+            },
+            StepKind::Move => Step::Move {
+                location: Coordinates::default(),
+            },
+            StepKind::Wait => Step::Wait {
+                millis: 200,
+                use_random: false,
+                max_millis: 500,
+            },
+            StepKind::WaitColor => Step::WaitColor {
+                condition: PixelCondition::default(),
+            },
+            StepKind::Key => Step::Key { code: 57 },
+            StepKind::Repeat => Step::Repeat { times: 2 },
+            StepKind::EndRepeat => Step::EndRepeat,
+            StepKind::Label => Step::Label {
+                name: "start".to_owned(),
+            },
+            StepKind::Jump => Step::Jump {
+                label: "start".to_owned(),
+            },
+        }
+    }
+}
+
+impl Step {
+    pub fn kind(&self) -> StepKind {
+        match self {
+            Step::Click { .. } => StepKind::Click,
+            Step::Move { .. } => StepKind::Move,
+            Step::Wait { .. } => StepKind::Wait,
+            Step::WaitColor { .. } => StepKind::WaitColor,
+            Step::Key { .. } => StepKind::Key,
+            Step::Repeat { .. } => StepKind::Repeat,
+            Step::EndRepeat => StepKind::EndRepeat,
+            Step::Label { .. } => StepKind::Label,
+            Step::Jump { .. } => StepKind::Jump,
+        }
+    }
+}
+
+/// Something for the clicker to do, produced by running a [`Program`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Click {
+        button: MouseButton,
+        action: ClickAction,
+        location: Option<Coordinates>,
+    },
+    Move(Coordinates),
+    Wait(Duration),
+    WaitColor(PixelCondition),
+    Key(u16),
+}
+
+/// An ordered list of steps, run from the top
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Sequence {
+    pub steps: Vec<Step>,
+}
+
+impl Sequence {
+    /// Checks that repeat blocks are balanced and that every jump has a label
+    pub fn compile(&self) -> Result<Program> {
+        let mut labels = HashMap::new();
+        let mut partner = vec![None; self.steps.len()];
+        let mut open = Vec::new();
+
+        for (index, step) in self.steps.iter().enumerate() {
+            match step {
+                Step::Repeat { .. } => open.push(index),
+                Step::EndRepeat => {
+                    let Some(start) = open.pop() else {
+                        bail!("Step {}: End repeat without a Repeat", index + 1);
+                    };
+                    partner[start] = Some(index);
+                    partner[index] = Some(start);
+                }
+                Step::Label { name } => {
+                    let first = labels.insert(name.clone(), index);
+                    if let Some(first) = first {
+                        bail!(
+                            "Step {}: label \"{name}\" is already used by step {}",
+                            index + 1,
+                            first + 1
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(start) = open.pop() {
+            bail!("Step {}: Repeat without an End repeat", start + 1);
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            if let Step::Jump { label } = step
+                && !labels.contains_key(label)
+            {
+                bail!("Step {}: no label called \"{label}\"", index + 1);
+            }
+        }
+
+        Ok(Program {
+            steps: self.steps.clone(),
+            labels,
+            partner,
+        })
+    }
+}
+
+/// A validated [`Sequence`], ready to run
+#[derive(Clone, Debug)]
+pub struct Program {
+    steps: Vec<Step>,
+    labels: HashMap<String, usize>,
+    /// The matching end of each Repeat, and the matching start of each End repeat
+    partner: Vec<Option<usize>>,
+}
+
+/// Steps through a [`Program`], turning it into [`Action`]s
+pub struct SequenceRunner<R = StdRng> {
+    program: Program,
+    position: usize,
+    /// Start of each repeat block we're inside, with the runs it has left
+    loops: Vec<(usize, u32)>,
+    rng: R,
+}
+
+impl SequenceRunner {
+    /// Creates a runner whose random waits follow from `seed`
+    pub fn from_seed(program: Program, seed: u64) -> Self {
+        Self::with_rng(program, StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> SequenceRunner<R> {
+    pub fn with_rng(program: Program, rng: R) -> Self {
+        Self {
+            program,
+            position: 0,
+            loops: Vec::new(),
+            rng,
+        }
+    }
+
+    /// Returns the next action, or `None` once the sequence has run off the end
+    pub fn next_action(&mut self) -> Result<Option<Action>> {
+        for _ in 0..MAX_CONTROL_STEPS {
+            let Some(step) = self.program.steps.get(self.position) else {
+                return Ok(None);
+            };
+            self.position += 1;
+
+            let action = match step {
+                Step::Click {
+                    button,
+                    action,
+                    use_location,
+                    location,
+                } => Action::Click {
+                    button: *button,
+                    action: *action,
+                    location: use_location.then_some(*location),
+                },
+                Step::Move { location } => Action::Move(*location),
+                Step::Wait {
+                    millis,
+                    use_random,
+                    max_millis,
+                } => {
+                    let millis = if *use_random && max_millis > millis {
+                        self.rng.random_range(*millis..=*max_millis)
+                    } else {
+                        *millis
+                    };
+                    Action::Wait(Duration::from_millis(millis as u64))
+                }
+                Step::WaitColor { condition } => Action::WaitColor(*condition),
+                Step::Key { code } => Action::Key(*code),
+                Step::Repeat { times } => {
+                    let start = self.position - 1;
+                    match times.checked_sub(1) {
+                        Some(left) => self.loops.push((start, left)),
+                        None => self.position = self.end_of(start) + 1,
+                    }
+                    continue;
+                }
+                Step::EndRepeat => {
+                    // A block entered by jumping into its middle only runs once
+                    let block_start = self.end_of(self.position - 1);
+                    if let Some((start, left)) = self.loops.last_mut()
+                        && *start == block_start
+                    {
+                        if *left > 0 {
+                            *left -= 1;
+                            self.position = *start + 1;
+                        } else {
+                            self.loops.pop();
+                        }
+                    }
+                    continue;
+                }
+                Step::Label { .. } => continue,
+                Step::Jump { label } => {
+                    let target = self.program.labels[label];
+                    // Leave any repeat blocks the jump lands outside of
+                    while let Some((start, _)) = self.loops.last()
+                        && !(*start..=self.end_of(*start)).contains(&target)
+                    {
+                        self.loops.pop();
+                    }
+                    self.position = target;
+                    continue;
+                }
+            };
+
+            return Ok(Some(action));
+        }
+
+        bail!("The sequence loops without doing anything")
+    }
+
+    fn end_of(&self, start: usize) -> usize {
+        self.program.partner[start].unwrap_or(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: u16) -> Step {
+        Step::Key { code }
+    }
+
+    fn repeat(times: u32) -> Step {
+        Step::Repeat { times }
+    }
+
+    fn label(name: &str) -> Step {
+        Step::Label {
+            name: name.to_owned(),
+        }
+    }
+
+    fn jump(label: &str) -> Step {
+        Step::Jump {
+            label: label.to_owned(),
+        }
+    }
+
+    fn compile(steps: Vec<Step>) -> Result<Program> {
+        Sequence { steps }.compile()
+    }
+
+    /// Runs `steps` for up to `limit` actions and returns the keys tapped
+    fn keys(steps: Vec<Step>, limit: usize) -> Vec<u16> {
+        let mut runner = SequenceRunner::from_seed(compile(steps).unwrap(), 0);
+        let mut keys = Vec::new();
+        while keys.len() < limit
+            && let Some(action) = runner.next_action().unwrap()
+        {
+            let Action::Key(code) = action else {
+                panic!("unexpected {action:?}");
+            };
+            keys.push(code);
+        }
+        keys
+    }
+
+    #[test]
+    fn nested_repeats_multiply() {
+        let steps = vec![
+            repeat(2),
+            key(1),
+            repeat(3),
+            key(2),
+            Step::EndRepeat,
+            Step::EndRepeat,
+            key(3),
+        ];
+        assert_eq!(keys(steps, 100), [1, 2, 2, 2, 1, 2, 2, 2, 3]);
+    }
+
+    #[test]
+    fn repeating_zero_times_skips_the_block() {
+        let steps = vec![repeat(0), key(1), Step::EndRepeat, key(2)];
+        assert_eq!(keys(steps, 100), [2]);
+    }
+
+    #[test]
+    fn forward_jumps_skip_steps() {
+        let steps = vec![key(1), jump("end"), key(2), label("end"), key(3)];
+        assert_eq!(keys(steps, 100), [1, 3]);
+    }
+
+    #[test]
+    fn backward_jumps_loop_and_restart_repeats() {
+        let steps = vec![
+            label("top"),
+            repeat(2),
+            key(1),
+            Step::EndRepeat,
+            key(2),
+            jump("top"),
+        ];
+        assert_eq!(keys(steps, 9), [1, 1, 2, 1, 1, 2, 1, 1, 2]);
+    }
+
+    #[test]
+    fn jumping_out_of_a_repeat_leaves_it() {
+        let steps = vec![
+            repeat(3),
+            key(1),
+            jump("out"),
+            Step::EndRepeat,
+            label("out"),
+            key(2),
+        ];
+        assert_eq!(keys(steps, 100), [1, 2]);
+    }
+
+    #[test]
+    fn unknown_labels_are_rejected() {
+        let error = compile(vec![key(1), jump("nowhere")]).unwrap_err();
+        assert_eq!(error.to_string(), "Step 2: no label called \"nowhere\"");
+    }
+
+    #[test]
+    fn duplicate_labels_are_rejected() {
+        let error = compile(vec![label("a"), key(1), label("a")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Step 3: label \"a\" is already used by step 1"
+        );
+    }
+
+    #[test]
+    fn unbalanced_repeats_are_rejected() {
+        let error = compile(vec![key(1), Step::EndRepeat]).unwrap_err();
+        assert_eq!(error.to_string(), "Step 2: End repeat without a Repeat");
+
+        let error = compile(vec![repeat(2), key(1)]).unwrap_err();
+        assert_eq!(error.to_string(), "Step 1: Repeat without an End repeat");
+    }
+
+    #[test]
+    fn loops_without_actions_are_stopped() {
+        for steps in [
+            vec![label("a"), jump("a")],
+            vec![repeat(u32::MAX), Step::EndRepeat],
+        ] {
+            let mut runner = SequenceRunner::from_seed(compile(steps).unwrap(), 0);
+            let error = runner.next_action().unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "The sequence loops without doing anything"
+            );
+        }
+    }
+
+    #[test]
+    fn random_waits_stay_in_range_and_follow_the_seed() {
+        let steps = vec![
+            label("top"),
+            Step::Wait {
+                millis: 100,
+                use_random: true,
+                max_millis: 200,
+            },
+            jump("top"),
+        ];
+        let waits = |seed| {
+            let mut runner = SequenceRunner::from_seed(compile(steps.clone()).unwrap(), seed);
+            (0..200)
+                .map(|_| match runner.next_action().unwrap() {
+                    Some(Action::Wait(wait)) => wait,
+                    action => panic!("unexpected {action:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = waits(7);
+        assert_eq!(first, waits(7));
+        assert!(first.iter().all(|wait| {
+            (Duration::from_millis(100)..=Duration::from_millis(200)).contains(wait)
+        }));
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of recent clicks kept for the interval and drift figures
+const RECENT_CLICKS: usize = 256;
+
+/// Timing of a single click, streamed from the clicker task to the UI
+#[derive(Clone, Copy, Debug)]
+pub struct ClickSample {
+    /// Clicks performed so far, including this one
+    pub count: u64,
+    /// When the click was due
+    pub scheduled: Instant,
+    /// When the click was actually sent
+    pub clicked: Instant,
+}
+
+/// Live figures for the current (or last) run, built from [`ClickSample`]s
+#[derive(Default)]
+pub struct ClickStats {
+    started: Option<Instant>,
+    stopped: Option<Instant>,
+    paused_at: Option<Instant>,
+    /// Time spent paused, left out of the elapsed time
+    paused_for: Duration,
+    target_interval: Option<Duration>,
+    clicks: u64,
+    last_click: Option<Instant>,
+    /// Recent inter-click intervals in milliseconds
+    intervals_ms: VecDeque<f64>,
+    /// Recent lateness of clicks relative to their schedule, in milliseconds
+    drift_ms: VecDeque<f64>,
+}
+
+impl ClickStats {
+    pub fn start(&mut self, started: Instant, target_interval: Option<Duration>) {
+        *self = Self {
+            started: Some(started),
+            target_interval,
+            ..Self::default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        self.resume();
+        if self.started.is_some() && self.stopped.is_none() {
+            self.stopped = Some(Instant::now());
+        }
+    }
+
+    pub fn set_target_interval(&mut self, target_interval: Duration) {
+        if self.started.is_some() {
+            self.target_interval = Some(target_interval);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(Instant::now());
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.paused_for += paused_at.elapsed();
+            // The gap across a pause isn't a real interval
+            self.last_click = None;
+        }
+    }
+
+    pub fn record(&mut self, sample: ClickSample) {
+        if let Some(last_click) = self.last_click {
+            push_recent(
+                &mut self.intervals_ms,
+                (sample.clicked - last_click).as_secs_f64() * 1000.0,
+            );
+        }
+        push_recent(
+            &mut self.drift_ms,
+            sample
+                .clicked
+                .saturating_duration_since(sample.scheduled)
+                .as_secs_f64()
+                * 1000.0,
+        );
+
+        self.clicks = sample.count;
+        self.last_click = Some(sample.clicked);
+    }
+
+    pub fn has_data(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub fn clicks(&self) -> u64 {
+        self.clicks
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.started {
+            Some(started) => self
+                .stopped
+                .or(self.paused_at)
+                .unwrap_or_else(Instant::now)
+                .saturating_duration_since(started)
+                .saturating_sub(self.paused_for),
+            None => Duration::ZERO,
+        }
+    }
+
+    /// Average clicks per second over the whole run
+    pub fn achieved_cps(&self) -> f64 {
+        let elapsed = self.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.clicks as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    pub fn target_cps(&self) -> Option<f64> {
+        self.target_interval
+            .map(|interval| 1.0 / interval.as_secs_f64())
+    }
+
+    /// Mean and standard deviation of recent intervals in milliseconds
+    pub fn interval_mean_std(&self) -> Option<(f64, f64)> {
+        mean_std(&self.intervals_ms)
+    }
+
+    /// Mean lateness of recent clicks behind their schedule in milliseconds
+    pub fn mean_drift_ms(&self) -> Option<f64> {
+        mean_std(&self.drift_ms).map(|(mean, _)| mean)
+    }
+
+    pub fn recent_intervals_ms(&self) -> impl Iterator<Item = f64> + Clone + '_ {
+        self.intervals_ms.iter().copied()
+    }
+}
+
+fn push_recent(values: &mut VecDeque<f64>, value: f64) {
+    if values.len() == RECENT_CLICKS {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+fn mean_std(values: &VecDeque<f64>) -> Option<(f64, f64)> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+    Some((mean, variance.sqrt()))
+}
+
+/// Buckets `values` into `bins` equal-width bins, returning each bin's center and count
+pub fn histogram(values: impl Iterator<Item = f64> + Clone, bins: usize) -> Vec<(f64, u32)> {
+    let (min, max) = values
+        .clone()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+    if bins == 0 || !min.is_finite() {
+        return Vec::new();
+    }
+
+    // Keep a sensible width when every value is the same
+    let width = ((max - min) / bins as f64).max(0.01);
+    let mut counts = vec![0u32; bins];
+    for value in values {
+        let index = (((value - min) / width) as usize).min(bins - 1);
+        counts[index] += 1;
+    }
+
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| (min + width * (i as f64 + 0.5), count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// A click `at` after `base`, sent `late` after it was due
+    fn sample(count: u64, base: Instant, at: Duration, late: Duration) -> ClickSample {
+        ClickSample {
+            count,
+            scheduled: base + at - late,
+            clicked: base + at,
+        }
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} vs {expected}");
+    }
+
+    #[test]
+    fn histogram_puts_values_on_bin_edges_in_the_upper_bin() {
+        let bins = histogram([0.0, 1.0, 2.0, 3.0, 4.0].into_iter(), 4);
+        // The largest value has nowhere higher to go, so it shares the last bin
+        assert_eq!(bins, [(0.5, 1), (1.5, 1), (2.5, 1), (3.5, 2)]);
+    }
+
+    #[test]
+    fn histogram_of_nothing_is_empty() {
+        assert!(histogram(std::iter::empty(), 10).is_empty());
+        assert!(histogram([1.0, 2.0].into_iter(), 0).is_empty());
+    }
+
+    #[test]
+    fn histogram_of_identical_values_has_one_full_bin() {
+        let bins = histogram([5.0; 8].into_iter(), 4);
+        assert_eq!(bins.len(), 4);
+        assert_eq!(bins[0].1, 8);
+        assert!(bins[1..].iter().all(|(_, count)| *count == 0));
+        assert_near(bins[0].0, 5.005);
+    }
+
+    #[test]
+    fn intervals_and_drift_come_from_the_samples() {
+        let base = Instant::now();
+        let mut stats = ClickStats::default();
+        stats.start(base, Some(ms(100)));
+        for i in 0..5 {
+            stats.record(sample(i + 1, base, ms(100 * i), ms(4 + i)));
+        }
+
+        let (mean, std) = stats.interval_mean_std().unwrap();
+        assert_near(mean, 100.0);
+        assert_near(std, 0.0);
+        assert_near(stats.mean_drift_ms().unwrap(), 6.0);
+        assert_eq!(stats.clicks(), 5);
+        assert_near(stats.target_cps().unwrap(), 10.0);
+    }
+
+    #[test]
+    fn early_clicks_dont_count_as_drift() {
+        let base = Instant::now();
+        let mut stats = ClickStats::default();
+        stats.record(ClickSample {
+            count: 1,
+            scheduled: base + ms(10),
+            clicked: base,
+        });
+        assert_eq!(stats.mean_drift_ms(), Some(0.0));
+        assert_eq!(stats.interval_mean_std(), None);
+    }
+
+    #[test]
+    fn the_gap_across_a_pause_isnt_an_interval() {
+        let base = Instant::now();
+        let mut stats = ClickStats::default();
+        stats.start(base, None);
+        stats.record(sample(1, base, ms(0), ms(0)));
+        stats.record(sample(2, base, ms(50), ms(0)));
+        stats.pause();
+        stats.resume();
+        stats.record(sample(3, base, ms(5000), ms(0)));
+        stats.record(sample(4, base, ms(5050), ms(0)));
+
+        assert_eq!(stats.recent_intervals_ms().count(), 2);
+        assert_near(stats.interval_mean_std().unwrap().0, 50.0);
+    }
+
+    #[test]
+    fn only_recent_clicks_are_kept() {
+        let base = Instant::now();
+        let mut stats = ClickStats::default();
+        for i in 0..(RECENT_CLICKS as u64 * 2) {
+            stats.record(sample(i + 1, base, ms(10 * i), ms(0)));
+        }
+        assert_eq!(stats.recent_intervals_ms().count(), RECENT_CLICKS);
+    }
+
+    #[test]
+    fn achieved_cps_leaves_out_pauses() {
+        let now = Instant::now();
+        let mut stats = ClickStats::default();
+        assert_eq!(stats.achieved_cps(), 0.0);
+
+        stats.start(now - Duration::from_secs(4), None);
+        stats.paused_for = Duration::from_secs(2);
+        stats.clicks = 30;
+        stats.stopped = Some(now);
+
+        assert_eq!(stats.elapsed(), Duration::from_secs(2));
+        assert_near(stats.achieved_cps(), 15.0);
+    }
+}
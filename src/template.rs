@@ -0,0 +1,420 @@
+//! Finding a reference image on screen, so clicks follow a target wherever its
+//! window happens to be
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    input::Coordinates,
+    screen::{Color, Image, Screen},
+};
+
+/// Coarse positions kept for a closer look at full resolution
+const CANDIDATES: usize = 32;
+/// Template side length aimed for when searching the downscaled screen
+const COARSE_SIDE: usize = 6;
+const MAX_FACTOR: usize = 8;
+
+/// When the screen is searched for the target
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum TargetSearch {
+    EveryClick,
+    OnceAtStart,
+}
+
+impl fmt::Display for TargetSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetSearch::EveryClick => write!(f, "Every click"),
+            TargetSearch::OnceAtStart => write!(f, "Once at start"),
+        }
+    }
+}
+
+impl TargetSearch {
+    pub fn all() -> [TargetSearch; 2] {
+        [TargetSearch::EveryClick, TargetSearch::OnceAtStart]
+    }
+}
+
+/// What to do when the target can't be found
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum MissAction {
+    Skip,
+    Stop,
+}
+
+impl fmt::Display for MissAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissAction::Skip => write!(f, "Skip the click"),
+            MissAction::Stop => write!(f, "Stop"),
+        }
+    }
+}
+
+impl MissAction {
+    pub fn all() -> [MissAction; 2] {
+        [MissAction::Skip, MissAction::Stop]
+    }
+}
+
+/// Clicks wherever a reference image shows up on screen
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ImageTarget {
+    /// PNG file with the image to look for
+    pub path: Option<PathBuf>,
+    /// Lowest similarity, from 0 to 1, that counts as a match
+    pub confidence: f32,
+    /// Largest random offset in pixels, on each axis, from the center of the match
+    pub scatter: u16,
+    pub search: TargetSearch,
+    pub on_miss: MissAction,
+}
+
+impl Default for ImageTarget {
+    fn default() -> Self {
+        Self {
+            path: None,
+            confidence: 0.9,
+            scatter: 0,
+            search: TargetSearch::EveryClick,
+            on_miss: MissAction::Skip,
+        }
+    }
+}
+
+/// Brightness of each pixel, which is all matching looks at
+struct Gray {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Gray {
+    fn from_colors(width: usize, height: usize, pixels: impl Iterator<Item = Color>) -> Self {
+        let data = pixels
+            .map(|Color { r, g, b }| ((r as u32 * 77 + g as u32 * 150 + b as u32 * 29) >> 8) as u8)
+            .collect();
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Averages each `factor` × `factor` block into one pixel
+    fn downscale(&self, factor: usize) -> Self {
+        let width = self.width / factor;
+        let height = self.height / factor;
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let sum: u32 = (0..factor)
+                    .flat_map(|dy| {
+                        let start = (y * factor + dy) * self.width + x * factor;
+                        &self.data[start..start + factor]
+                    })
+                    .map(|&value| value as u32)
+                    .sum();
+                data.push((sum / (factor * factor) as u32) as u8);
+            }
+        }
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Sum of absolute differences with `template` placed at (`x`, `y`),
+    /// giving up early once it reaches `limit`
+    fn difference(&self, template: &Gray, x: usize, y: usize, limit: u64) -> u64 {
+        let mut total = 0;
+        for row in 0..template.height {
+            let start = (y + row) * self.width + x;
+            let screen = &self.data[start..start + template.width];
+            let wanted = &template.data[row * template.width..(row + 1) * template.width];
+            total += screen
+                .iter()
+                .zip(wanted)
+                .map(|(a, b)| a.abs_diff(*b) as u64)
+                .sum::<u64>();
+            if total >= limit {
+                break;
+            }
+        }
+        total
+    }
+
+    /// Every placement of `template`, best first, keeping only the best `keep`
+    fn best_placements(&self, template: &Gray, keep: usize) -> Vec<(u64, usize, usize)> {
+        let mut best: Vec<(u64, usize, usize)> = Vec::with_capacity(keep + 1);
+        for y in 0..=self.height - template.height {
+            for x in 0..=self.width - template.width {
+                let limit = match best.last() {
+                    Some(&(worst, ..)) if best.len() == keep => worst,
+                    _ => u64::MAX,
+                };
+                let difference = self.difference(template, x, y, limit);
+                if difference < limit {
+                    let index = best.partition_point(|&(other, ..)| other <= difference);
+                    best.insert(index, (difference, x, y));
+                    best.truncate(keep);
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Best place the template was found, whether or not it's good enough
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match {
+    pub center: Coordinates,
+    /// Similarity from 0 to 1, where 1 is a pixel-perfect match
+    pub confidence: f32,
+}
+
+/// A reference image prepared for searching
+pub struct Template {
+    full: Gray,
+    coarse: Gray,
+    factor: usize,
+}
+
+impl Template {
+    pub fn load(path: &Path) -> Result<Self> {
+        let image = image::open(path)
+            .with_context(|| format!("Couldn't open {}", path.display()))?
+            .to_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if width == 0 || height == 0 {
+            bail!("{} is empty", path.display());
+        }
+
+        Ok(Self::from_colors(
+            width,
+            height,
+            image.pixels().map(|pixel| Color {
+                r: pixel[0],
+                g: pixel[1],
+                b: pixel[2],
+            }),
+        ))
+    }
+
+    fn from_colors(width: usize, height: usize, pixels: impl Iterator<Item = Color>) -> Self {
+        let full = Gray::from_colors(width, height, pixels);
+        let factor = (width.min(height) / COARSE_SIDE).clamp(1, MAX_FACTOR);
+        Self {
+            coarse: full.downscale(factor),
+            full,
+            factor,
+        }
+    }
+
+    /// Finds the most similar spot in `image`: first on a downscaled copy, then
+    /// around the best few spots at full resolution
+    pub fn find(&self, image: &Image) -> Option<Match> {
+        let screen = Gray::from_colors(
+            image.width as usize,
+            image.height as usize,
+            image.pixels().iter().copied(),
+        );
+        if screen.width < self.full.width || screen.height < self.full.height {
+            return None;
+        }
+
+        let best = if self.factor == 1 {
+            screen.best_placements(&self.full, 1).first().copied()
+        } else {
+            let candidates = screen
+                .downscale(self.factor)
+                .best_placements(&self.coarse, CANDIDATES);
+            let max_x = screen.width - self.full.width;
+            let max_y = screen.height - self.full.height;
+
+            let mut best: Option<(u64, usize, usize)> = None;
+            for (_, x, y) in candidates {
+                // A coarse spot covers `factor` full pixels, so look a little either side
+                let (x, y) = (x * self.factor, y * self.factor);
+                for y in y.saturating_sub(self.factor)..=(y + self.factor).min(max_y) {
+                    for x in x.saturating_sub(self.factor)..=(x + self.factor).min(max_x) {
+                        let limit = best.map_or(u64::MAX, |(difference, ..)| difference);
+                        let difference = screen.difference(&self.full, x, y, limit);
+                        if difference < limit {
+                            best = Some((difference, x, y));
+                        }
+                    }
+                }
+            }
+            best
+        };
+
+        let (difference, x, y) = best?;
+        let pixels = (self.full.width * self.full.height) as f32;
+        Some(Match {
+            center: Coordinates {
+                x: image.origin.x + (x + self.full.width / 2) as i32,
+                y: image.origin.y + (y + self.full.height / 2) as i32,
+            },
+            confidence: 1.0 - difference as f32 / (255.0 * pixels),
+        })
+    }
+}
+
+/// Looks for an [`ImageTarget`] on screen while the clicker runs
+pub struct TargetFinder {
+    target: ImageTarget,
+    template: Template,
+    screen: Screen,
+    rng: StdRng,
+    /// Where the target was found, kept when it's only searched for once
+    found: Option<Coordinates>,
+}
+
+impl TargetFinder {
+    pub fn new(target: ImageTarget, seed: u64) -> Result<Self> {
+        let path = target
+            .path
+            .as_deref()
+            .context("No target image has been chosen")?;
+        Ok(Self {
+            template: Template::load(path)?,
+            screen: Screen::connect()?,
+            rng: StdRng::seed_from_u64(seed),
+            target,
+            found: None,
+        })
+    }
+
+    pub fn on_miss(&self) -> MissAction {
+        self.target.on_miss
+    }
+
+    /// Returns where to click, or the best match if it wasn't good enough
+    pub fn locate(&mut self) -> Result<Located> {
+        let center = match self.found {
+            Some(center) if self.target.search == TargetSearch::OnceAtStart => center,
+            _ => {
+                let (width, height) = self.screen.size();
+                let image = self.screen.capture(Coordinates::default(), width, height)?;
+                match self.template.find(&image) {
+                    Some(found) if found.confidence >= self.target.confidence => {
+                        self.found = Some(found.center);
+                        found.center
+                    }
+                    best => return Ok(Located::Missed(best)),
+                }
+            }
+        };
+
+        let scatter = self.target.scatter as i32;
+        Ok(Located::At(Coordinates {
+            x: center.x + self.rng.random_range(-scatter..=scatter),
+            y: center.y + self.rng.random_range(-scatter..=scatter),
+        }))
+    }
+
+    /// Explains a miss, for the status shown when it stops the run
+    pub fn describe_miss(&self, best: Option<Match>) -> String {
+        match best {
+            Some(best) => format!(
+                "Target image not found: the best match is {:.0}% similar, below the {:.0}% needed",
+                best.confidence * 100.0,
+                self.target.confidence * 100.0
+            ),
+            None => "Target image not found: it's larger than the screen".to_owned(),
+        }
+    }
+}
+
+/// Outcome of looking for the target
+pub enum Located {
+    At(Coordinates),
+    Missed(Option<Match>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Random gray levels, so every spot of the image looks different
+    fn noise(rng: &mut StdRng, count: usize) -> Vec<Color> {
+        (0..count)
+            .map(|_| {
+                let level = rng.random();
+                Color {
+                    r: level,
+                    g: level,
+                    b: level,
+                }
+            })
+            .collect()
+    }
+
+    /// A noisy screen with a noisy template pasted at (`x`, `y`)
+    fn scene(width: u32, height: u32, side: u32, x: u32, y: u32) -> (Image, Template) {
+        let mut rng = StdRng::seed_from_u64(9);
+        let wanted = noise(&mut rng, (side * side) as usize);
+        let mut pixels = noise(&mut rng, (width * height) as usize);
+        for row in 0..side {
+            let start = ((y + row) * width + x) as usize;
+            let from = (row * side) as usize;
+            pixels[start..start + side as usize]
+                .copy_from_slice(&wanted[from..from + side as usize]);
+        }
+
+        let origin = Coordinates { x: 100, y: 50 };
+        let side = side as usize;
+        (
+            Image::new(origin, width, height, pixels),
+            Template::from_colors(side, side, wanted.into_iter()),
+        )
+    }
+
+    #[test]
+    fn finds_a_small_template_pixel_by_pixel() {
+        let (image, template) = scene(80, 60, 5, 41, 17);
+        assert_eq!(template.factor, 1);
+
+        let found = template.find(&image).unwrap();
+        assert_eq!(found.center, Coordinates { x: 143, y: 69 });
+        assert_eq!(found.confidence, 1.0);
+    }
+
+    #[test]
+    fn finds_a_large_template_through_the_coarse_search() {
+        let (image, template) = scene(320, 200, 36, 203, 121);
+        assert_eq!(template.factor, 6);
+
+        let found = template.find(&image).unwrap();
+        assert_eq!(found.center, Coordinates { x: 321, y: 189 });
+        assert_eq!(found.confidence, 1.0);
+    }
+
+    #[test]
+    fn misses_a_template_that_isnt_there() {
+        let (image, _) = scene(320, 200, 36, 203, 121);
+        let mut rng = StdRng::seed_from_u64(10);
+        let elsewhere = Template::from_colors(36, 36, noise(&mut rng, 36 * 36).into_iter());
+
+        let best = elsewhere.find(&image).unwrap();
+        assert!(best.confidence < 0.9, "{best:?}");
+    }
+
+    #[test]
+    fn templates_larger_than_the_screen_are_never_found() {
+        let (image, _) = scene(30, 20, 5, 0, 0);
+        let (_, template) = scene(80, 60, 25, 0, 0);
+        assert_eq!(template.find(&image), None);
+    }
+}
@@ -0,0 +1,167 @@
+use eframe::egui;
+use egui_plot::{Bar, BarChart, Line, Plot, PlotPoints};
+
+use crate::{
+    input::Coordinates,
+    interval::TimeInterval,
+    schedule::TimeOfDay,
+    screen::{Color, PixelCondition},
+};
+
+/// H:M:S:MS:µs drag values editing a [`TimeInterval`] in place
+pub fn time_interval_editor(ui: &mut egui::Ui, interval: &mut TimeInterval) {
+    ui.horizontal(|ui| {
+        ui.label("H:");
+        ui.add(
+            egui::DragValue::new(&mut interval.hours)
+                .speed(0.1)
+                .range(0..=23),
+        );
+        ui.label("M:");
+        ui.add(
+            egui::DragValue::new(&mut interval.minutes)
+                .speed(0.1)
+                .range(0..=59),
+        );
+        ui.label("S:");
+        ui.add(
+            egui::DragValue::new(&mut interval.seconds)
+                .speed(0.1)
+                .range(0..=59),
+        );
+        ui.label("MS:");
+        ui.add(
+            egui::DragValue::new(&mut interval.milliseconds)
+                .speed(1.0)
+                .range(0..=999),
+        );
+        ui.label("µs:");
+        ui.add(
+            egui::DragValue::new(&mut interval.microseconds)
+                .speed(1.0)
+                .range(0..=999),
+        );
+    });
+}
+
+/// X and Y drag values editing screen [`Coordinates`] in place
+pub fn coordinates_editor(ui: &mut egui::Ui, coords: &mut Coordinates) {
+    ui.horizontal(|ui| {
+        ui.label("X:");
+        ui.add(
+            egui::DragValue::new(&mut coords.x)
+                .speed(1.0)
+                .range(0..=i32::MAX),
+        );
+        ui.label("Y:");
+        ui.add(
+            egui::DragValue::new(&mut coords.y)
+                .speed(1.0)
+                .range(0..=i32::MAX),
+        );
+    });
+}
+
+/// Location, color, tolerance and area of a [`PixelCondition`], with a button
+/// to pick the location and color from the screen. `picking` is the countdown
+/// to a pick in progress; returns whether the button was clicked.
+pub fn pixel_condition_editor(
+    ui: &mut egui::Ui,
+    condition: &mut PixelCondition,
+    picking: Option<u64>,
+) -> bool {
+    ui.horizontal(|ui| {
+        coordinates_editor(ui, &mut condition.location);
+
+        let Color { r, g, b } = condition.color;
+        let mut rgb = [r, g, b];
+        ui.color_edit_button_srgb(&mut rgb);
+        let [r, g, b] = rgb;
+        condition.color = Color { r, g, b };
+
+        ui.label("±");
+        ui.add(egui::DragValue::new(&mut condition.tolerance).speed(0.5))
+            .on_hover_text("How far each channel may be from the color");
+        ui.label("Area:");
+        ui.add(
+            egui::DragValue::new(&mut condition.radius)
+                .speed(0.1)
+                .range(0..=50)
+                .suffix(" px"),
+        )
+        .on_hover_text("Pixels around the location that may match too");
+
+        let label = match picking {
+            Some(secs) => format!("Picking in {secs}…"),
+            None => "Pick".to_owned(),
+        };
+        ui.add_enabled(picking.is_none(), egui::Button::new(label))
+            .on_hover_text("Reads the point and color under the pointer after a short countdown")
+            .clicked()
+    })
+    .inner
+}
+
+/// H:M:S drag values editing a [`TimeOfDay`] in place
+pub fn time_of_day_editor(ui: &mut egui::Ui, time: &mut TimeOfDay) {
+    ui.horizontal(|ui| {
+        ui.label("H:");
+        ui.add(
+            egui::DragValue::new(&mut time.hours)
+                .speed(0.1)
+                .range(0..=23),
+        );
+        ui.label("M:");
+        ui.add(
+            egui::DragValue::new(&mut time.minutes)
+                .speed(0.1)
+                .range(0..=59),
+        );
+        ui.label("S:");
+        ui.add(
+            egui::DragValue::new(&mut time.seconds)
+                .speed(0.1)
+                .range(0..=59),
+        );
+    });
+}
+
+/// Compact bar chart of interval counts from [`crate::stats::histogram`]
+pub fn interval_histogram(ui: &mut egui::Ui, id_salt: &str, bins: &[(f64, u32)]) {
+    let bar_width = match bins {
+        [first, second, ..] => (second.0 - first.0) * 0.9,
+        _ => 1.0,
+    };
+    let bars = bins
+        .iter()
+        .map(|(center, count)| Bar::new(*center, *count as f64).width(bar_width))
+        .collect();
+
+    Plot::new(id_salt)
+        .height(90.0)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .show_axes([true, false])
+        .x_axis_formatter(|mark, _| format!("{} ms", mark.value))
+        .show(ui, |plot| plot.bar_chart(BarChart::new("Intervals", bars)));
+}
+
+/// Line plot of successive intervals, to show rhythm and hesitation spikes over time
+pub fn interval_timeline(ui: &mut egui::Ui, id_salt: &str, intervals_ms: &[f64]) {
+    let points: PlotPoints = intervals_ms
+        .iter()
+        .enumerate()
+        .map(|(i, ms)| [i as f64, *ms])
+        .collect();
+
+    Plot::new(id_salt)
+        .height(90.0)
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .allow_boxed_zoom(false)
+        .y_axis_formatter(|mark, _| format!("{} ms", mark.value))
+        .show(ui, |plot| plot.line(Line::new("Interval", points)));
+}